@@ -1,4 +1,6 @@
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::Formatter;
 use std::fs;
 use std::io;
@@ -7,7 +9,7 @@ use std::path::Path;
 
 struct File {
     name: String,
-    size: u32
+    size: u64
 }
 
 pub struct FileSystem {
@@ -66,25 +68,305 @@ impl FileSystem {
         }
     }
 
-    fn create_file_if_not_exists(&mut self, file_size: u32, file_name: &str) {
+    fn create_file_if_not_exists(&mut self, file_size: u64, file_name: &str) {
         let current_dir = self.current_dir();
         if !current_dir.files.contains_key(file_name) &&
             !current_dir.directories.contains_key(file_name) {
             current_dir.insert_file(file_size, file_name);
+            self.invalidate_sizes();
         }
     }
 
-    pub fn get_size(&self) -> u32 {
+    /// Drops the cached sizes of the current directory and all of its ancestors.
+    fn invalidate_sizes(&mut self) {
+        let mut current_dir = &mut self.root_dir;
+        current_dir.size.set(None);
+        for item in &self.current_path {
+            current_dir = current_dir.get_dir_mut(item).unwrap();
+            current_dir.size.set(None);
+        }
+    }
+
+    pub fn get_size(&self) -> u64 {
         self.root_dir.get_size()
     }
 
-    pub fn get_small_directory_size(&self) -> u32 {
+    pub fn get_small_directory_size(&self) -> u64 {
         self.root_dir.get_small_directory_size()
     }
 
-    pub fn get_all_directory_sizes(&self) -> Vec<u32> {
+    pub fn get_all_directory_sizes(&self) -> Vec<u64> {
         self.root_dir.get_all_directory_sizes()
     }
+
+    /// Size of the file or directory at the absolute `path` (e.g. "/a/e"), if it exists.
+    pub fn size_of(&self, path: &str) -> Option<u64> {
+        let mut components = split_path(path);
+        let last = match components.pop() {
+            Some(name) => name,
+            None => return Some(self.root_dir.get_size())
+        };
+        let mut dir = &self.root_dir;
+        for name in components {
+            dir = dir.directories.get(name)?;
+        }
+        if let Some(directory) = dir.directories.get(last) {
+            Some(directory.get_size())
+        } else {
+            dir.files.get(last).map(|file| file.size)
+        }
+    }
+
+    /// Iterates over every entry of the tree (depth first, sorted by name)
+    /// for which `predicate(path, kind, size)` holds.
+    pub fn find<P>(&self, predicate: P) -> impl Iterator<Item = (String, EntryKind, u64)>
+        where P: Fn(&str, EntryKind, u64) -> bool {
+        let mut entries = Vec::new();
+        self.root_dir.collect_entries("/", &mut entries);
+        entries.into_iter()
+            .filter(move |(path, kind, size)| predicate(path, *kind, *size))
+    }
+
+    /// du-style report: one "size<TAB>path" line per directory, children before parents.
+    pub fn du(&self) -> String {
+        let mut directories: Vec<(String, u64)> = self
+            .find(|_, kind, _| kind == EntryKind::Directory)
+            .map(|(path, _, size)| (path, size))
+            .collect();
+        directories.reverse();
+        let mut report = String::new();
+        for (path, size) in directories {
+            report.push_str(&format!("{}\t{}\n", size, path));
+        }
+        report
+    }
+
+    /// Minimal `$ cd`/`$ ls` transcript that rebuilds this tree with `parse_file_system`.
+    pub fn to_transcript(&self) -> String {
+        let mut transcript = String::from("$ cd /\n");
+        self.root_dir.write_transcript(&mut transcript);
+        while transcript.ends_with("$ cd ..\n") {
+            transcript.truncate(transcript.len() - "$ cd ..\n".len());
+        }
+        transcript
+    }
+
+    /// Walks a real directory on disk and builds the same tree with the actual
+    /// file sizes. Symlinks and special files are skipped, and so are entries
+    /// that cannot be read; an unreadable subdirectory shows up empty. Only
+    /// failing to list `path` itself is an error. A hard-linked file is
+    /// counted once for every link to it.
+    pub fn from_directory<P: AsRef<Path>>(path: P) -> io::Result<FileSystem> {
+        let mut file_system = FileSystem::new();
+        file_system.root_dir.mount(path.as_ref())?;
+        Ok(file_system)
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(&self.root_dir.to_node())
+    }
+
+    /// Rebuilds a file system from `to_json` output. Directory sizes in the
+    /// JSON are informational only and get recomputed from the files.
+    pub fn from_json(json: &str) -> serde_json::Result<FileSystem> {
+        let node: Node = serde_json::from_str(json)?;
+        let mut file_system = FileSystem::new();
+        match node {
+            Node::Dir { children, .. } => {
+                for child in children {
+                    file_system.root_dir.insert_node(child);
+                }
+                Ok(file_system)
+            }
+            Node::File { .. } => Err(serde::de::Error::custom("the root must be a directory"))
+        }
+    }
+
+    /// Renders the tree in the puzzle's notation, with siblings sorted by `order`.
+    pub fn tree(&self, order: SortOrder) -> String {
+        let mut output = String::new();
+        self.root_dir.render_tree(0, order, &mut output);
+        output
+    }
+}
+
+/// JSON representation of a tree entry.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Node {
+    File {
+        name: String,
+        size: u64
+    },
+    Dir {
+        name: String,
+        size: u64,
+        children: Vec<Node>
+    }
+}
+
+/// Plans which directories to delete so that `space_needed` bytes are free
+/// on a disk of `total_space` bytes.
+pub struct CleanupPlanner {
+    total_space: u64,
+    space_needed: u64
+}
+
+impl CleanupPlanner {
+    pub fn new(total_space: u64, space_needed: u64) -> Self {
+        CleanupPlanner {
+            total_space,
+            space_needed
+        }
+    }
+
+    pub fn space_to_free_up(&self, file_system: &FileSystem) -> u64 {
+        let space_free = self.total_space.saturating_sub(file_system.get_size());
+        self.space_needed.saturating_sub(space_free)
+    }
+
+    /// The smallest single directory whose deletion frees enough space.
+    pub fn best_single_directory(&self, file_system: &FileSystem) -> Option<(String, u64)> {
+        let space_to_free_up = self.space_to_free_up(file_system);
+        file_system
+            .find(|_, kind, size| kind == EntryKind::Directory && space_to_free_up <= size)
+            .map(|(path, _, size)| (path, size))
+            .min_by_key(|(_, size)| *size)
+    }
+
+    /// The set of non-nested directories that frees enough space while deleting
+    /// the fewest bytes in total, or `None` if there is none or finding it
+    /// would mean tracking more than `MAX_TRACKED_SUMS` distinct sums.
+    ///
+    /// Directories are decided on in pre-order; deleting one skips its whole
+    /// subtree. The sums below the target are tracked in a sparse bitset,
+    /// with the directory that first reached each sum so the set can be
+    /// rebuilt. Memory grows with the number of distinct sums, not with the
+    /// bytes to free.
+    pub fn best_directory_set(&self, file_system: &FileSystem) -> Option<(Vec<String>, u64)> {
+        let target = self.space_to_free_up(file_system);
+        if target == 0 {
+            return Some((Vec::new(), 0));
+        }
+        let mut directories = Vec::new();
+        file_system.root_dir.collect_preorder("/", &mut directories);
+        let mut reached = SparseBits::from([(0, 1)]);
+        let mut via = HashMap::new();
+        // sums reachable before each directory whose deletion is still pending,
+        // innermost last; a deletion takes effect after the directory's subtree
+        let mut pending: Vec<(usize, SparseBits)> = Vec::new();
+        // smallest sum of at least the target, as (sum, last directory, sum before it)
+        let mut best: Option<(u64, usize, u64)> = None;
+        for index in 0..=directories.len() {
+            while pending.last().is_some_and(|(directory, _)| directories[*directory].2 == index) {
+                let (directory, before) = pending.pop().unwrap();
+                add_shifted_sums(&mut reached, &mut via, &before, directories[directory].1, directory, target);
+                if MAX_TRACKED_SUMS < via.len() {
+                    return None;
+                }
+            }
+            if let Some((_, size, _)) = directories.get(index) {
+                if let Some(before) = first_set_bit(&reached, target.saturating_sub(*size)) {
+                    if best.is_none_or(|(sum, _, _)| before + size < sum) {
+                        best = Some((before + size, index, before));
+                    }
+                }
+                pending.push((index, reached.clone()));
+            }
+        }
+        let (sum, last, mut rest) = best?;
+        let mut chosen = vec![last];
+        while rest > 0 {
+            let directory = via[&rest];
+            chosen.push(directory);
+            rest -= directories[directory].1;
+        }
+        chosen.sort();
+        let paths = chosen.into_iter().map(|directory| directories[directory].0.clone()).collect();
+        Some((paths, sum))
+    }
+}
+
+/// Upper bound for the distinct sums `best_directory_set` keeps track of.
+pub const MAX_TRACKED_SUMS: usize = 1 << 22;
+
+/// Set of numbers as 64 bit words keyed by `number / 64`; only words with a
+/// bit set are stored.
+type SparseBits = BTreeMap<u64, u64>;
+
+impl Default for CleanupPlanner {
+    fn default() -> Self {
+        CleanupPlanner::new(70000000, 30000000)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EntryKind {
+    File,
+    Directory
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortOrder {
+    Name,
+    /// Largest entries first, ties broken by name.
+    Size
+}
+
+fn node_name(node: &Node) -> &str {
+    match node {
+        Node::File { name, .. } | Node::Dir { name, .. } => name
+    }
+}
+
+/// Sets every bit `sum + shift` below `limit` for the bits of `before`, and
+/// records `directory` for the sums that were not reached yet.
+fn add_shifted_sums(reached: &mut SparseBits, via: &mut HashMap<u64, usize>, before: &SparseBits, shift: u64, directory: usize, limit: u64) {
+    let (word_shift, bit_shift) = (shift / 64, shift % 64);
+    for (&word, &bits) in before {
+        let mut parts = vec![(word + word_shift, bits << bit_shift)];
+        if bit_shift > 0 {
+            parts.push((word + word_shift + 1, bits >> (64 - bit_shift)));
+        }
+        for (word, shifted) in parts {
+            if shifted == 0 || limit <= word * 64 {
+                continue;
+            }
+            let existing = reached.entry(word).or_insert(0);
+            let mut new = shifted & !*existing;
+            while new != 0 {
+                let sum = word * 64 + new.trailing_zeros() as u64;
+                new &= new - 1;
+                if sum < limit {
+                    via.insert(sum, directory);
+                    *existing |= 1 << (sum % 64);
+                }
+            }
+            if *existing == 0 {
+                reached.remove(&word);
+            }
+        }
+    }
+}
+
+/// The first number in `bits` that is at least `from`.
+fn first_set_bit(bits: &SparseBits, from: u64) -> Option<u64> {
+    bits.range(from / 64..).find_map(|(&word, &bits)| {
+        let bits = if word == from / 64 { bits & (u64::MAX << (from % 64)) } else { bits };
+        (bits != 0).then(|| word * 64 + bits.trailing_zeros() as u64)
+    })
+}
+
+fn split_path(path: &str) -> Vec<&str> {
+    path.split('/').filter(|name| !name.is_empty()).collect()
+}
+
+fn join_path(parent: &str, name: &str) -> String {
+    if parent.ends_with('/') {
+        format!("{}{}", parent, name)
+    } else {
+        format!("{}/{}", parent, name)
+    }
 }
 
 impl std::fmt::Display for FileSystem {
@@ -96,7 +378,9 @@ impl std::fmt::Display for FileSystem {
 struct Directory {
     name: String,
     directories: HashMap<String, Directory>,
-    files: HashMap<String, File>
+    files: HashMap<String, File>,
+    /// total size of the subtree, filled in lazily by `get_size`
+    size: Cell<Option<u64>>
 }
 
 impl Directory {
@@ -104,7 +388,8 @@ impl Directory {
         Directory {
             name,
             directories: HashMap::new(),
-            files: HashMap::new()
+            files: HashMap::new(),
+            size: Cell::new(None)
         }
     }
 
@@ -113,21 +398,22 @@ impl Directory {
     }
 
     fn insert_dir(&mut self, dir_name: &str) {
-        self.directories.insert(dir_name.to_string(), Directory{
-            name: dir_name.to_string(),
-            directories: HashMap::new(),
-            files: HashMap::new()
-        });
+        self.directories.insert(dir_name.to_string(), Directory::new(dir_name.to_string()));
     }
 
-    fn insert_file(&mut self, file_size: u32, file_name: &str) {
+    fn insert_file(&mut self, file_size: u64, file_name: &str) {
         self.files.insert(file_name.to_string(), File {
             name: file_name.to_string(),
             size: file_size
         });
     }
 
-    fn get_size(&self) -> u32 {
+    /// Size of the subtree. The first call computes all sizes below in one
+    /// post-order pass and caches them, so later calls are O(1).
+    fn get_size(&self) -> u64 {
+        if let Some(size) = self.size.get() {
+            return size;
+        }
         let mut sum = 0;
         for file in self.files.values() {
             sum += file.size;
@@ -135,10 +421,11 @@ impl Directory {
         for directory in self.directories.values() {
             sum += directory.get_size();
         }
+        self.size.set(Some(sum));
         sum
     }
 
-    fn get_small_directory_size(&self) -> u32 {
+    fn get_small_directory_size(&self) -> u64 {
         let mut sum = 0;
         let size = self.get_size();
         if size <= 100000 {
@@ -150,14 +437,131 @@ impl Directory {
         sum
     }
 
-    fn get_all_directory_sizes(&self) -> Vec<u32> {
-        let mut vec: Vec<u32> = Vec::new();
+    fn get_all_directory_sizes(&self) -> Vec<u64> {
+        let mut vec: Vec<u64> = Vec::new();
         vec.push(self.get_size());
         for directory in self.directories.values() {
             vec.append(&mut directory.get_all_directory_sizes());
         }
         vec
     }
+
+    /// Appends this directory and all below it in pre-order (children sorted by
+    /// name) as (path, size, index after the last directory of the subtree).
+    fn collect_preorder(&self, path: &str, directories: &mut Vec<(String, u64, usize)>) {
+        let index = directories.len();
+        directories.push((path.to_string(), self.get_size(), 0));
+        let mut names: Vec<&String> = self.directories.keys().collect();
+        names.sort();
+        for name in names {
+            self.directories[name].collect_preorder(&join_path(path, name), directories);
+        }
+        directories[index].2 = directories.len();
+    }
+
+    fn mount(&mut self, path: &Path) -> io::Result<()> {
+        for entry in fs::read_dir(path)?.flatten() {
+            let Ok(metadata) = fs::symlink_metadata(entry.path()) else {
+                continue;
+            };
+            let name = entry.file_name().to_string_lossy().to_string();
+            if metadata.is_dir() {
+                self.insert_dir(&name);
+                // keeps whatever could be read below it
+                let _ = self.get_dir_mut(&name).unwrap().mount(&entry.path());
+            } else if metadata.is_file() {
+                self.insert_file(metadata.len(), &name);
+            }
+        }
+        Ok(())
+    }
+
+    fn write_transcript(&self, transcript: &mut String) {
+        if self.directories.is_empty() && self.files.is_empty() {
+            return;
+        }
+        transcript.push_str("$ ls\n");
+        let mut dir_names: Vec<&String> = self.directories.keys().collect();
+        dir_names.sort();
+        for name in &dir_names {
+            transcript.push_str(&format!("dir {}\n", name));
+        }
+        let mut files: Vec<&File> = self.files.values().collect();
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+        for file in files {
+            transcript.push_str(&format!("{} {}\n", file.size, file.name));
+        }
+        for name in dir_names {
+            let directory = &self.directories[name];
+            if !directory.directories.is_empty() || !directory.files.is_empty() {
+                transcript.push_str(&format!("$ cd {}\n", name));
+                directory.write_transcript(transcript);
+                transcript.push_str("$ cd ..\n");
+            }
+        }
+    }
+
+    fn to_node(&self) -> Node {
+        let mut children: Vec<Node> = self.directories.values().map(|d| d.to_node()).collect();
+        children.extend(self.files.values().map(|file| Node::File {
+            name: file.name.clone(),
+            size: file.size
+        }));
+        children.sort_by(|a, b| node_name(a).cmp(node_name(b)));
+        Node::Dir {
+            name: self.name.clone(),
+            size: self.get_size(),
+            children
+        }
+    }
+
+    fn insert_node(&mut self, node: Node) {
+        match node {
+            Node::File { name, size } => self.insert_file(size, &name),
+            Node::Dir { name, children, .. } => {
+                self.insert_dir(&name);
+                let directory = self.get_dir_mut(&name).unwrap();
+                for child in children {
+                    directory.insert_node(child);
+                }
+            }
+        }
+    }
+
+    fn collect_entries(&self, path: &str, entries: &mut Vec<(String, EntryKind, u64)>) {
+        entries.push((path.to_string(), EntryKind::Directory, self.get_size()));
+        let mut names: Vec<&String> = self.directories.keys().chain(self.files.keys()).collect();
+        names.sort();
+        for name in names {
+            let child_path = join_path(path, name);
+            if let Some(directory) = self.directories.get(name) {
+                directory.collect_entries(&child_path, entries);
+            } else if let Some(file) = self.files.get(name) {
+                entries.push((child_path, EntryKind::File, file.size));
+            }
+        }
+    }
+
+    fn render_tree(&self, depth: usize, order: SortOrder, output: &mut String) {
+        output.push_str(&format!("{}- {} (dir, size={})\n", "  ".repeat(depth), self.name, self.get_size()));
+        let mut children: Vec<(&str, u64, Option<&Directory>)> = Vec::new();
+        for directory in self.directories.values() {
+            children.push((&directory.name, directory.get_size(), Some(directory)));
+        }
+        for file in self.files.values() {
+            children.push((&file.name, file.size, None));
+        }
+        match order {
+            SortOrder::Name => children.sort_by(|a, b| a.0.cmp(b.0)),
+            SortOrder::Size => children.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)))
+        }
+        for (name, size, directory) in children {
+            match directory {
+                Some(directory) => directory.render_tree(depth + 1, order, output),
+                None => output.push_str(&format!("{}- {} (file, size={})\n", "  ".repeat(depth + 1), name, size))
+            }
+        }
+    }
 }
 
 impl std::fmt::Display for Directory {
@@ -168,36 +572,44 @@ impl std::fmt::Display for Directory {
 
 pub fn read_in_file_system(file_path: &str) -> Result<FileSystem, &str> {
     if let Ok(lines) = read_lines(file_path) {
-        let mut file_system = FileSystem::new();
-        let mut create_mode = false;
-        for line in lines {
-            if let Ok(l) = line{
-                let v = l.split(" ").collect::<Vec<_>>();
-                if v[0] == "$" {
-                    // command mode (ls, cd)
-                    create_mode = false;
-                    if v[1] == "cd" {
-                        file_system.cd(v[2].to_string());
-                    } else if v[1] == "ls" {
-                        create_mode = true;
-                    }
-                } else if create_mode {
-                    // output mode for creating directories
-                    if v[0] == "dir" {
-                        file_system.create_dir_if_not_exists(v[1]);
-                    } else {
-                        file_system.create_file_if_not_exists(v[0].parse().unwrap(), v[1]);
-                    }
-                }
-            }
-        }
-        file_system.cd_root();
-        Ok(file_system)
+        Ok(build_file_system(lines.map_while(Result::ok)))
     } else {
         Err("no valid file")
     }
 }
 
+/// Builds a file system from a `$ cd`/`$ ls` transcript held in memory.
+pub fn parse_file_system(transcript: &str) -> FileSystem {
+    build_file_system(transcript.lines().map(|l| l.to_string()))
+}
+
+fn build_file_system<I>(lines: I) -> FileSystem
+    where I: Iterator<Item = String> {
+    let mut file_system = FileSystem::new();
+    let mut create_mode = false;
+    for l in lines {
+        let v = l.split(" ").collect::<Vec<_>>();
+        if v[0] == "$" {
+            // command mode (ls, cd)
+            create_mode = false;
+            if v[1] == "cd" {
+                file_system.cd(v[2].to_string());
+            } else if v[1] == "ls" {
+                create_mode = true;
+            }
+        } else if create_mode {
+            // output mode for creating directories
+            if v[0] == "dir" {
+                file_system.create_dir_if_not_exists(v[1]);
+            } else {
+                file_system.create_file_if_not_exists(v[0].parse().unwrap(), v[1]);
+            }
+        }
+    }
+    file_system.cd_root();
+    file_system
+}
+
 fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<fs::File>>>
     where P: AsRef<Path>, {
     let file = fs::File::open(filename)?;
@@ -206,7 +618,7 @@ fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<fs::File>>>
 
 #[cfg(test)]
 mod tests {
-    use crate::read_in_file_system;
+    use crate::{parse_file_system, read_in_file_system, CleanupPlanner, EntryKind, FileSystem, SortOrder};
 
     #[test]
     fn test_size() {
@@ -226,4 +638,141 @@ mod tests {
         assert_eq!(filesystem.root_dir.get_size(), 48381165);
         assert_eq!(filesystem.root_dir.get_small_directory_size(), 95437)
     }
+
+    #[test]
+    fn test_queries() {
+        let filesystem = read_in_file_system("input_test").unwrap();
+        assert_eq!(filesystem.size_of("/"), Some(48381165));
+        assert_eq!(filesystem.size_of("/a/e"), Some(584));
+        assert_eq!(filesystem.size_of("/a/e/i"), Some(584));
+        assert_eq!(filesystem.size_of("/d/k"), Some(7214296));
+        assert_eq!(filesystem.size_of("/x"), None);
+        let small: Vec<_> = filesystem
+            .find(|_, kind, size| kind == EntryKind::Directory && size <= 100000)
+            .collect();
+        assert_eq!(small, vec![
+            ("/a".to_string(), EntryKind::Directory, 94853),
+            ("/a/e".to_string(), EntryKind::Directory, 584)
+        ]);
+        assert_eq!(filesystem.du(), "24933642\t/d\n584\t/a/e\n94853\t/a\n48381165\t/\n");
+        let tree = filesystem.tree(SortOrder::Size);
+        assert!(tree.starts_with("- / (dir, size=48381165)\n  - d (dir, size=24933642)\n"));
+        assert!(filesystem.tree(SortOrder::Name).contains("\n    - e (dir, size=584)\n      - i (file, size=584)\n"));
+    }
+
+    #[test]
+    fn test_size_cache_invalidation() {
+        let mut filesystem = read_in_file_system("input_test").unwrap();
+        assert_eq!(filesystem.get_size(), 48381165);
+        filesystem.cd("a".to_string());
+        filesystem.cd("e".to_string());
+        filesystem.create_file_if_not_exists(16, "new");
+        assert_eq!(filesystem.current_dir().get_size(), 600);
+        assert_eq!(filesystem.size_of("/a"), Some(94869));
+        assert_eq!(filesystem.get_size(), 48381181);
+        assert_eq!(filesystem.size_of("/d"), Some(24933642));
+    }
+
+    #[test]
+    fn test_cleanup_planner() {
+        let filesystem = read_in_file_system("input_test").unwrap();
+        let planner = CleanupPlanner::default();
+        assert_eq!(planner.space_to_free_up(&filesystem), 8381165);
+        assert_eq!(planner.best_single_directory(&filesystem), Some(("/d".to_string(), 24933642)));
+        assert_eq!(planner.best_directory_set(&filesystem), Some((vec!["/d".to_string()], 24933642)));
+
+        let mut filesystem = FileSystem::new();
+        for (dir, size) in [("x", 60), ("y", 50), ("z", 200)] {
+            filesystem.create_dir_if_not_exists(dir);
+            filesystem.cd(dir.to_string());
+            filesystem.create_file_if_not_exists(size, "file");
+            filesystem.cd("..".to_string());
+        }
+        let planner = CleanupPlanner::new(400, 190);
+        assert_eq!(planner.space_to_free_up(&filesystem), 100);
+        assert_eq!(planner.best_single_directory(&filesystem), Some(("/z".to_string(), 200)));
+        assert_eq!(planner.best_directory_set(&filesystem), Some((vec!["/x".to_string(), "/y".to_string()], 110)));
+        assert_eq!(CleanupPlanner::new(1000, 10).best_directory_set(&filesystem), Some((Vec::new(), 0)));
+    }
+
+    #[test]
+    fn test_cleanup_planner_on_input() {
+        let filesystem = read_in_file_system("input").unwrap();
+        let planner = CleanupPlanner::default();
+        assert_eq!(planner.space_to_free_up(&filesystem), 1272621);
+        assert_eq!(planner.best_single_directory(&filesystem).unwrap().1, 1300850);
+        let (paths, size) = planner.best_directory_set(&filesystem).unwrap();
+        assert_eq!(size, 1272621);
+        assert_eq!(paths.iter().map(|path| filesystem.size_of(path).unwrap()).sum::<u64>(), size);
+        for path in &paths {
+            assert!(!paths.iter().any(|other| other != path && other.starts_with(&format!("{}/", path))));
+        }
+    }
+
+    #[test]
+    fn test_cleanup_planner_with_large_directories() {
+        let gib: u64 = 1024 * 1024 * 1024;
+        let mut filesystem = FileSystem::new();
+        for (dir, size) in [("movies", 30 * gib), ("music", 25 * gib), ("photos", 60 * gib)] {
+            filesystem.create_dir_if_not_exists(dir);
+            filesystem.cd(dir.to_string());
+            filesystem.create_file_if_not_exists(size, "file");
+            filesystem.cd("..".to_string());
+        }
+        let planner = CleanupPlanner::new(200 * gib, 140 * gib);
+        assert_eq!(planner.space_to_free_up(&filesystem), 55 * gib);
+        assert_eq!(planner.best_single_directory(&filesystem), Some(("/photos".to_string(), 60 * gib)));
+        assert_eq!(planner.best_directory_set(&filesystem),
+                   Some((vec!["/movies".to_string(), "/music".to_string()], 55 * gib)));
+    }
+
+    #[test]
+    fn test_serialization_round_trip() {
+        let filesystem = read_in_file_system("input_test").unwrap();
+        let transcript = filesystem.to_transcript();
+        assert!(transcript.starts_with("$ cd /\n$ ls\ndir a\ndir d\n14848514 b.txt\n8504156 c.dat\n$ cd a\n"));
+        assert!(!transcript.ends_with("$ cd ..\n"));
+        let rebuilt = parse_file_system(&transcript);
+        assert_eq!(rebuilt.tree(SortOrder::Name), filesystem.tree(SortOrder::Name));
+        assert_eq!(rebuilt.to_transcript(), transcript);
+
+        let json = filesystem.to_json().unwrap();
+        let rebuilt = FileSystem::from_json(&json).unwrap();
+        assert_eq!(rebuilt.tree(SortOrder::Name), filesystem.tree(SortOrder::Name));
+        assert_eq!(rebuilt.to_json().unwrap(), json);
+        assert!(FileSystem::from_json("{\"type\": \"nope\"}").is_err());
+        let error = FileSystem::from_json("{\"type\": \"file\", \"name\": \"a\", \"size\": 1}").err().unwrap();
+        assert!(error.to_string().contains("root must be a directory"));
+
+        // sizes beyond u32 add up without wrapping
+        let three_gib = 3 * 1024 * 1024 * 1024;
+        let json = format!(
+            "{{\"type\": \"dir\", \"name\": \"/\", \"size\": 0, \"children\": [{{\"type\": \"dir\", \"name\": \"big\", \"size\": 0, \"children\": [\
+            {{\"type\": \"file\", \"name\": \"x\", \"size\": {0}}}, {{\"type\": \"file\", \"name\": \"y\", \"size\": {0}}}]}}]}}",
+            three_gib
+        );
+        let large = FileSystem::from_json(&json).unwrap();
+        assert_eq!(large.get_size(), 2 * three_gib);
+        assert_eq!(large.du(), format!("{0}\t/big\n{0}\t/\n", 2 * three_gib));
+    }
+
+    #[test]
+    fn test_from_directory() {
+        let root = std::env::temp_dir().join(format!("day_07_mount_{}", std::process::id()));
+        std::fs::create_dir_all(root.join("a/e")).unwrap();
+        std::fs::create_dir_all(root.join("empty")).unwrap();
+        std::fs::write(root.join("b.txt"), [0u8; 100]).unwrap();
+        std::fs::write(root.join("a/f"), [0u8; 20]).unwrap();
+        std::fs::write(root.join("a/e/i"), [0u8; 3]).unwrap();
+        std::fs::hard_link(root.join("a/f"), root.join("g")).unwrap();
+        let filesystem = FileSystem::from_directory(&root);
+        std::fs::remove_dir_all(&root).unwrap();
+        let filesystem = filesystem.unwrap();
+        // the hard link g counts the 20 bytes of a/f a second time
+        assert_eq!(filesystem.get_size(), 143);
+        assert_eq!(filesystem.size_of("/a"), Some(23));
+        assert_eq!(filesystem.size_of("/a/e/i"), Some(3));
+        assert_eq!(filesystem.size_of("/empty"), Some(0));
+        assert!(FileSystem::from_directory(root.join("missing")).is_err());
+    }
 }
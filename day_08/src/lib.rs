@@ -2,150 +2,400 @@ use std::fs;
 use std::io;
 use std::io::BufRead;
 use std::path::Path;
-use std::slice::Iter;
 
-enum Direction {
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
     North,
     East,
     South,
     West
 }
 
-pub struct Grid {
-    rows: Vec<Vec<u32>>,
-    cols: Vec<Vec<u32>>
+/// Rectangular grid stored row by row in a single `Vec`.
+pub struct Grid<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>
 }
 
-impl Grid {
-    fn new() -> Self {
-        Grid {
-            rows: Vec::new(),
-            cols: Vec::new()
+impl<T> Grid<T> {
+    /// Builds a grid from its rows, or `None` if they differ in length.
+    pub fn from_rows(rows: Vec<Vec<T>>) -> Option<Self> {
+        let height = rows.len();
+        let width = rows.first().map_or(0, |row| row.len());
+        if rows.iter().any(|row| row.len() != width) {
+            return None;
         }
+        Some(Grid {
+            width,
+            height,
+            cells: rows.into_iter().flatten().collect()
+        })
     }
 
-    fn add_element(&mut self, row_id: usize, col_id: usize, value: u32) {
-        while self.rows.len() <= row_id {
-            self.rows.push(Vec::new());
-        }
-        while self.cols.len() <= col_id {
-            self.cols.push(Vec::new());
-        }
-        self.rows[row_id].push(value);
-        self.cols[col_id].push(value);
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
     }
 
-    fn is_visible(&self, row_id: usize, col_id: usize) -> bool {
-        let rows_len = self.rows.len();
-        let cols_len = self.cols.len();
-        if row_id == 0 || row_id == (rows_len - 1) || col_id == 0 || col_id == (cols_len - 1) {
-            true
+    pub fn get(&self, row_id: usize, col_id: usize) -> Option<&T> {
+        if row_id < self.height && col_id < self.width {
+            Some(&self.cells[row_id * self.width + col_id])
         } else {
-            let val = self.rows[row_id][col_id];
-            if self.rows[row_id][0..col_id].iter().max().unwrap() < &val ||
-                self.rows[row_id][col_id+1..].iter().max().unwrap() < &val ||
-                self.cols[col_id][0..row_id].iter().max().unwrap() < &val ||
-                self.cols[col_id][row_id+1..].iter().max().unwrap() < &val {
-                true
+            None
+        }
+    }
+
+    pub fn row(&self, row_id: usize) -> &[T] {
+        &self.cells[row_id * self.width..(row_id + 1) * self.width]
+    }
+
+    /// Panics if `col_id` is out of bounds, like `row`.
+    pub fn col(&self, col_id: usize) -> impl DoubleEndedIterator<Item = &T> + ExactSizeIterator {
+        assert!(col_id < self.width, "grid column out of bounds");
+        self.cells[col_id..].iter().step_by(self.width)
+    }
+
+    /// Coordinates of the (up to four) orthogonal neighbors of a cell.
+    pub fn neighbors(&self, row_id: usize, col_id: usize) -> impl Iterator<Item = (usize, usize)> {
+        let (height, width) = (self.height, self.width);
+        [(-1, 0), (0, 1), (1, 0), (0, -1)].into_iter().filter_map(move |(dr, dc): (isize, isize)| {
+            let r = row_id.checked_add_signed(dr)?;
+            let c = col_id.checked_add_signed(dc)?;
+            if r < height && c < width {
+                Some((r, c))
             } else {
-                false
+                None
             }
+        })
+    }
+
+    /// Iterates over all cells as `((row, col), value)` in row-major order.
+    pub fn iter(&self) -> impl Iterator<Item = ((usize, usize), &T)> {
+        let width = self.width;
+        self.cells.iter().enumerate().map(move |(i, v)| ((i / width, i % width), v))
+    }
+
+    pub fn map<U, F: FnMut(&T) -> U>(&self, f: F) -> Grid<U> {
+        Grid {
+            width: self.width,
+            height: self.height,
+            cells: self.cells.iter().map(f).collect()
         }
     }
+}
 
-    pub fn number_of_visible_elements(&self) -> u32 {
-        let mut sum = 0;
-        for i in 0..self.rows.len() {
-            for j in 0..self.rows[i].len() {
-                if self.is_visible(i, j) {
-                    sum += 1
-                }
+impl<T> std::ops::Index<(usize, usize)> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, (row_id, col_id): (usize, usize)) -> &T {
+        self.get(row_id, col_id).expect("grid index out of bounds")
+    }
+}
+
+impl<T> std::ops::IndexMut<(usize, usize)> for Grid<T> {
+    fn index_mut(&mut self, (row_id, col_id): (usize, usize)) -> &mut T {
+        assert!(row_id < self.height && col_id < self.width, "grid index out of bounds");
+        &mut self.cells[row_id * self.width + col_id]
+    }
+}
+
+impl<T: Copy + Ord> Grid<T> {
+    /// Marks every cell that is visible from at least one edge. Each of the four
+    /// directions is a single sweep that keeps the running maximum, so the whole
+    /// map costs O(rows·cols).
+    pub fn visibility_map(&self) -> Grid<bool> {
+        let mut visible = self.map(|_| false);
+        for row_id in 0..self.height {
+            let mut highest = None;
+            for col_id in 0..self.width {
+                Self::sweep_cell(self[(row_id, col_id)], &mut highest, &mut visible[(row_id, col_id)]);
+            }
+            let mut highest = None;
+            for col_id in (0..self.width).rev() {
+                Self::sweep_cell(self[(row_id, col_id)], &mut highest, &mut visible[(row_id, col_id)]);
             }
         }
-        sum
+        for col_id in 0..self.width {
+            let mut highest = None;
+            for row_id in 0..self.height {
+                Self::sweep_cell(self[(row_id, col_id)], &mut highest, &mut visible[(row_id, col_id)]);
+            }
+            let mut highest = None;
+            for row_id in (0..self.height).rev() {
+                Self::sweep_cell(self[(row_id, col_id)], &mut highest, &mut visible[(row_id, col_id)]);
+            }
+        }
+        visible
     }
 
-    fn view_distance(&self, row_id: usize, col_id: usize, value: u32, direction: Direction) -> u32 {
-        let mut sum = 0;
-        let mut add_routine = |iterator: Iter<u32>, reverse: bool| {
-            if reverse {
-                for v in iterator.rev() {
-                    sum += 1;
-                    if &value <= v {
-                        break
-                    }
-                }
-            } else {
-                for v in iterator {
-                    sum += 1;
-                    if &value <= v {
+    fn sweep_cell(value: T, highest: &mut Option<T>, visible: &mut bool) {
+        if highest.is_none_or(|h| h < value) {
+            *visible = true;
+            *highest = Some(value);
+        }
+    }
+
+    pub fn number_of_visible_elements(&self) -> u32 {
+        self.visibility_map().cells.iter().filter(|v| **v).count() as u32
+    }
+
+    /// Cell coordinates grouped into lines such that looking in `direction`
+    /// means looking back towards the start of the line.
+    fn lines_towards(&self, direction: Direction) -> Vec<Vec<(usize, usize)>> {
+        match direction {
+            Direction::North => (0..self.width).map(|c| (0..self.height).map(|r| (r, c)).collect()).collect(),
+            Direction::South => (0..self.width).map(|c| (0..self.height).rev().map(|r| (r, c)).collect()).collect(),
+            Direction::West => (0..self.height).map(|r| (0..self.width).map(|c| (r, c)).collect()).collect(),
+            Direction::East => (0..self.height).map(|r| (0..self.width).rev().map(|c| (r, c)).collect()).collect()
+        }
+    }
+
+    /// Viewing distance of every tree in `direction`: the number of trees up to
+    /// and including the first one at least as tall, or up to the edge.
+    /// A monotonic stack per line keeps this linear in the grid size.
+    pub fn view_distance_map(&self, direction: Direction) -> Grid<u32> {
+        let mut distances = self.map(|_| 0);
+        for line in self.lines_towards(direction) {
+            let mut stack: Vec<usize> = Vec::new();
+            for (i, &coordinates) in line.iter().enumerate() {
+                let value = self[coordinates];
+                while let Some(&top) = stack.last() {
+                    if self[line[top]] < value {
+                        stack.pop();
+                    } else {
                         break
                     }
                 }
+                distances[coordinates] = match stack.last() {
+                    Some(&blocker) => (i - blocker) as u32,
+                    None => i as u32
+                };
+                stack.push(i);
             }
-            sum
-        };
-        match direction {
-            Direction::North => {
-                add_routine(self.cols[col_id][..row_id].iter(), true)
+        }
+        distances
+    }
+
+    /// Product of the four viewing distances for every tree.
+    pub fn scenic_score_map(&self) -> Grid<u32> {
+        let mut scores = self.map(|_| 1);
+        for direction in [Direction::North, Direction::East, Direction::South, Direction::West] {
+            let distances = self.view_distance_map(direction);
+            for (score, distance) in scores.cells.iter_mut().zip(distances.cells) {
+                *score *= distance;
             }
-            Direction::South => {
-                add_routine(self.cols[col_id][row_id+1..].iter(), false)
+        }
+        scores
+    }
+
+    /// The `k` best trees as `((row, col), score)`, best first; ties keep row-major order.
+    pub fn top_scenic_spots(&self, k: usize) -> Vec<((usize, usize), u32)> {
+        let scores = self.scenic_score_map();
+        let mut spots: Vec<((usize, usize), u32)> = scores.iter().map(|(c, s)| (c, *s)).collect();
+        spots.sort_by_key(|spot| std::cmp::Reverse(spot.1));
+        spots.truncate(k);
+        spots
+    }
+
+    pub fn best_scenic_spot(&self) -> Option<((usize, usize), u32)> {
+        self.top_scenic_spots(1).pop()
+    }
+
+    pub fn highest_scenic_score(&self) -> u32 {
+        self.best_scenic_spot().map_or(0, |(_, score)| score)
+    }
+}
+
+/// Unit steps of the 8 compass directions as `(row delta, col delta)`, starting north, clockwise.
+pub const COMPASS: [(isize, isize); 8] = [(-1, 0), (-1, 1), (0, 1), (1, 1), (1, 0), (1, -1), (0, -1), (-1, -1)];
+
+impl<T: Copy + Into<f64>> Grid<T> {
+    /// Trees visible to an observer standing on `origin` with eyes at
+    /// `eye_height`, looking along the ray `(row delta, col delta)` until the
+    /// edge. A tree is visible when its top rises above the sight line to every
+    /// tree in front of it.
+    pub fn visible_along(&self, origin: (usize, usize), eye_height: f64, direction: (isize, isize)) -> Vec<(usize, usize)> {
+        if direction == (0, 0) {
+            return Vec::new();
+        }
+        // aim far enough that the ray leaves the grid before reaching the target
+        let reach = (self.width + self.height) as isize;
+        let target = (origin.0 as isize + direction.0 * reach, origin.1 as isize + direction.1 * reach);
+        let mut visible = Vec::new();
+        let mut max_slope = f64::NEG_INFINITY;
+        for cell in line_cells(origin, target).into_iter().skip(1) {
+            let Some(cell) = self.cell_in_bounds(cell) else {
+                break
+            };
+            let slope = self.slope(origin, eye_height, cell);
+            if max_slope < slope {
+                visible.push(cell);
+                max_slope = slope;
             }
-            Direction::East => {
-                add_routine(self.rows[row_id][col_id+1..].iter(), false)
+        }
+        visible
+    }
+
+    /// Trees visible from `origin` along any of the 8 compass directions, in row-major order.
+    pub fn visible_from(&self, origin: (usize, usize), eye_height: f64) -> Vec<(usize, usize)> {
+        let mut visible: Vec<(usize, usize)> = COMPASS
+            .iter()
+            .flat_map(|&direction| self.visible_along(origin, eye_height, direction))
+            .collect();
+        visible.sort();
+        visible
+    }
+
+    /// Whether the top of the tree at `target` can be seen from `origin`;
+    /// false if either lies outside the grid.
+    pub fn has_line_of_sight(&self, origin: (usize, usize), eye_height: f64, target: (usize, usize)) -> bool {
+        if origin == target || self.get(origin.0, origin.1).is_none() || self.get(target.0, target.1).is_none() {
+            return false;
+        }
+        let cells = line_cells(origin, (target.0 as isize, target.1 as isize));
+        let target_slope = self.slope(origin, eye_height, target);
+        cells[1..cells.len() - 1].iter().all(|&cell| {
+            let cell = (cell.0 as usize, cell.1 as usize);
+            self.slope(origin, eye_height, cell) < target_slope
+        })
+    }
+
+    fn cell_in_bounds(&self, (row_id, col_id): (isize, isize)) -> Option<(usize, usize)> {
+        if 0 <= row_id && (row_id as usize) < self.height && 0 <= col_id && (col_id as usize) < self.width {
+            Some((row_id as usize, col_id as usize))
+        } else {
+            None
+        }
+    }
+
+    fn slope(&self, origin: (usize, usize), eye_height: f64, cell: (usize, usize)) -> f64 {
+        let dr = cell.0 as f64 - origin.0 as f64;
+        let dc = cell.1 as f64 - origin.1 as f64;
+        (self[cell].into() - eye_height) / (dr * dr + dc * dc).sqrt()
+    }
+}
+
+/// Cells crossed by the straight line from `from` to `to` (Bresenham), both ends included.
+fn line_cells(from: (usize, usize), to: (isize, isize)) -> Vec<(isize, isize)> {
+    let (mut r, mut c) = (from.0 as isize, from.1 as isize);
+    let dr = (to.0 - r).abs();
+    let dc = -(to.1 - c).abs();
+    let step_r = if r < to.0 { 1 } else { -1 };
+    let step_c = if c < to.1 { 1 } else { -1 };
+    let mut error = dr + dc;
+    let mut cells = vec![(r, c)];
+    while (r, c) != to {
+        let doubled = 2 * error;
+        if dc <= doubled {
+            error += dc;
+            r += step_r;
+        }
+        if doubled <= dr {
+            error += dr;
+            c += step_c;
+        }
+        cells.push((r, c));
+    }
+    cells
+}
+
+/// How tree heights are written in the input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeightFormat {
+    /// one digit per tree, as in the puzzle input
+    Digits,
+    /// heights of any size separated by whitespace
+    Whitespace
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParseError {
+    NoValidFile,
+    /// `line` and `column` are 1-based positions in the input
+    InvalidHeight { line: usize, column: usize, token: String },
+    RaggedRow { line: usize, expected: usize, found: usize }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::NoValidFile => write!(f, "no valid file"),
+            ParseError::InvalidHeight { line, column, token } => {
+                write!(f, "invalid height {:?} at line {}, column {}", token, line, column)
             }
-            Direction::West => {
-                add_routine(self.rows[row_id][..col_id].iter(), true)
+            ParseError::RaggedRow { line, expected, found } => {
+                write!(f, "line {} has {} trees, expected {}", line, found, expected)
             }
         }
     }
+}
 
-    fn scenic_score(&self, row_id: usize, col_id: usize) -> u32 {
-        let value = self.rows[row_id][col_id];
-        self.view_distance(row_id, col_id, value, Direction::North) *
-            self.view_distance(row_id, col_id, value, Direction::East) *
-            self.view_distance(row_id, col_id, value, Direction::South) *
-            self.view_distance(row_id, col_id, value, Direction::West)
+impl std::error::Error for ParseError {}
+
+pub fn read_in_file_system(file_path: &str) -> Result<Grid<u32>, ParseError> {
+    read_in_file_system_with(file_path, HeightFormat::Digits)
+}
+
+pub fn read_in_file_system_with(file_path: &str, format: HeightFormat) -> Result<Grid<u32>, ParseError> {
+    if let Ok(lines) = read_lines(file_path) {
+        parse_lines(lines.map_while(Result::ok), format)
+    } else {
+        Err(ParseError::NoValidFile)
     }
+}
 
-    pub fn highest_scenic_score(&self) -> u32 {
-        let mut best = 0;
-        for i in 0..self.rows.len() {
-            for j in 0..self.rows[i].len() {
-                let score = self.scenic_score(i, j);
-                if best < score {
-                    best = score;
-                }
+/// Parses a rectangular height grid; blank lines are ignored.
+pub fn parse_grid(input: &str, format: HeightFormat) -> Result<Grid<u32>, ParseError> {
+    parse_lines(input.lines().map(|l| l.to_string()), format)
+}
+
+fn parse_lines<I>(lines: I, format: HeightFormat) -> Result<Grid<u32>, ParseError>
+    where I: Iterator<Item = String> {
+    let mut rows: Vec<Vec<u32>> = Vec::new();
+    for (line_number, l) in lines.enumerate() {
+        let line = line_number + 1;
+        if l.trim().is_empty() {
+            continue
+        }
+        let row = match format {
+            HeightFormat::Digits => parse_digit_row(&l, line)?,
+            HeightFormat::Whitespace => parse_whitespace_row(&l, line)?
+        };
+        if let Some(first) = rows.first() {
+            if first.len() != row.len() {
+                return Err(ParseError::RaggedRow { line, expected: first.len(), found: row.len() });
             }
         }
-        best
+        rows.push(row);
     }
+    Ok(Grid::from_rows(rows).expect("rows were checked to be of equal length"))
 }
 
-pub fn read_in_file_system(file_path: &str) -> Result<Grid, &str> {
-    if let Ok(lines) = read_lines(file_path) {
-        let mut line_number = 0;
-        let mut grid = Grid::new();
-        for line in lines {
-            if let Ok(l) = line{
-                let mut symbol_number = 0;
-                for c in l.chars() {
-                    match c.to_digit(10) {
-                        Some(value) => {
-                            grid.add_element(line_number, symbol_number, value);
-                            symbol_number += 1;
-                        }
-                        None => break
-                    }
-                }
+fn parse_digit_row(l: &str, line: usize) -> Result<Vec<u32>, ParseError> {
+    l.trim_end().chars().enumerate().map(|(column, c)| {
+        c.to_digit(10).ok_or(ParseError::InvalidHeight { line, column: column + 1, token: c.to_string() })
+    }).collect()
+}
+
+fn parse_whitespace_row(l: &str, line: usize) -> Result<Vec<u32>, ParseError> {
+    let mut row = Vec::new();
+    let mut offset = 0;
+    for token in l.split_whitespace() {
+        let start = l[offset..].find(token).unwrap() + offset;
+        offset = start + token.len();
+        match token.parse() {
+            Ok(value) => row.push(value),
+            Err(_) => {
+                let column = l[..start].chars().count() + 1;
+                return Err(ParseError::InvalidHeight { line, column, token: token.to_string() });
             }
-            line_number += 1;
         }
-        Ok(grid)
-    } else {
-        Err("no valid file")
     }
+    Ok(row)
 }
 
 fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<fs::File>>>
@@ -156,7 +406,7 @@ fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<fs::File>>>
 
 #[cfg(test)]
 mod tests {
-    use crate::read_in_file_system;
+    use crate::{parse_grid, read_in_file_system, Direction, Grid, HeightFormat, ParseError};
 
     #[test]
     fn test_visible() {
@@ -169,4 +419,79 @@ mod tests {
         let grid = read_in_file_system("input_test").unwrap();
         assert_eq!(grid.highest_scenic_score(), 8);
     }
+
+    #[test]
+    fn test_grid() {
+        let grid = read_in_file_system("input_test").unwrap();
+        assert_eq!((grid.height(), grid.width()), (5, 5));
+        assert_eq!(grid.row(1), &[2, 5, 5, 1, 2]);
+        assert_eq!(grid.col(3).copied().collect::<Vec<_>>(), vec![7, 1, 3, 4, 9]);
+        assert_eq!(grid.col(4).len(), 5);
+        assert_eq!(grid.neighbors(0, 4).collect::<Vec<_>>(), vec![(1, 4), (0, 3)]);
+        assert_eq!(grid.get(5, 0), None);
+        let visible = grid.visibility_map();
+        assert!(visible[(1, 1)] && !visible[(1, 3)] && !visible[(2, 2)]);
+        assert!(Grid::from_rows(vec![vec![1, 2], vec![3]]).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "grid column out of bounds")]
+    fn test_col_out_of_bounds() {
+        let grid = Grid::from_rows(vec![vec![1, 2], vec![3, 4]]).unwrap();
+        grid.col(2).count();
+    }
+
+    #[test]
+    #[should_panic(expected = "grid column out of bounds")]
+    fn test_col_of_empty_grid() {
+        let grid: Grid<u8> = Grid::from_rows(vec![vec![], vec![]]).unwrap();
+        grid.col(0).count();
+    }
+
+    #[test]
+    fn test_scenic_score_map() {
+        let grid = read_in_file_system("input_test").unwrap();
+        assert_eq!(grid.view_distance_map(Direction::North)[(3, 2)], 2);
+        assert_eq!(grid.view_distance_map(Direction::West)[(3, 2)], 2);
+        assert_eq!(grid.view_distance_map(Direction::South)[(3, 2)], 1);
+        assert_eq!(grid.view_distance_map(Direction::East)[(3, 2)], 2);
+        let scores = grid.scenic_score_map();
+        assert_eq!(scores[(1, 2)], 4);
+        assert_eq!(scores[(0, 0)], 0);
+        assert_eq!(grid.best_scenic_spot(), Some(((3, 2), 8)));
+        assert_eq!(grid.top_scenic_spots(2), vec![((3, 2), 8), ((2, 1), 6)]);
+    }
+
+    #[test]
+    fn test_line_of_sight() {
+        let grid = read_in_file_system("input_test").unwrap();
+        // standing on the 3 in the middle with eyes just above it
+        assert_eq!(grid.visible_along((2, 2), 3.5, (-1, 0)), vec![(1, 2)]);
+        assert_eq!(grid.visible_along((2, 2), 3.5, (0, 1)), vec![(2, 3)]);
+        assert_eq!(grid.visible_along((2, 2), 3.5, (1, 1)), vec![(3, 3)]);
+        assert_eq!(grid.visible_along((2, 2), 3.5, (-1, 2)), vec![(1, 3), (1, 4)]);
+        assert_eq!(grid.visible_along((2, 2), 3.5, (0, 0)), vec![]);
+        let visible = grid.visible_from((2, 2), 3.5);
+        assert_eq!(visible.len(), 10);
+        assert!(visible.contains(&(0, 4)) && !visible.contains(&(0, 0)) && !visible.contains(&(0, 2)));
+        assert!(grid.has_line_of_sight((2, 2), 3.5, (0, 4)));
+        assert!(!grid.has_line_of_sight((2, 2), 3.5, (4, 4)));
+        assert!(!grid.has_line_of_sight((2, 2), 3.5, (0, 2)));
+        assert!(!grid.has_line_of_sight((2, 2), 3.5, (2, 2)));
+        assert!(!grid.has_line_of_sight((9, 9), 3.5, (0, 0)));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse_grid("123\n4x6\n", HeightFormat::Digits).err(),
+                   Some(ParseError::InvalidHeight { line: 2, column: 2, token: "x".to_string() }));
+        assert_eq!(parse_grid("123\n45\n", HeightFormat::Digits).err(),
+                   Some(ParseError::RaggedRow { line: 2, expected: 3, found: 2 }));
+        let grid = parse_grid("10 200 3\n\n4  5 66\n", HeightFormat::Whitespace).unwrap();
+        assert_eq!(grid.row(0), &[10, 200, 3]);
+        assert_eq!(grid.row(1), &[4, 5, 66]);
+        assert_eq!(parse_grid("1 2\n3 -4\n", HeightFormat::Whitespace).err(),
+                   Some(ParseError::InvalidHeight { line: 2, column: 3, token: "-4".to_string() }));
+        assert_eq!(read_in_file_system("missing").err(), Some(ParseError::NoValidFile));
+    }
 }
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::io;
 use std::io::BufRead;
@@ -9,14 +9,168 @@ struct Point {
     y: i32
 }
 
+/// `count` unit steps in the direction `(x, y)`; `count` fits in an `i32`.
 pub struct Move {
     x: i32,
-    y: i32
+    y: i32,
+    count: u32
 }
 
 pub struct Walker {
     snake: Vec<Point>,
-    visited_points: HashMap<(i32, i32), bool>
+    /// per tracked knot: how often it arrived at each position (the start
+    /// counts once); `None` for knots that are not tracked
+    visits: Vec<Option<HashMap<(i32, i32), u32>>>,
+    trajectory: Option<Trajectory>,
+    follow_rule: Box<dyn FollowRule>,
+    moved: bool
+}
+
+/// Decides how a knot reacts to the knot ahead of it.
+pub trait FollowRule {
+    /// Displacement of a knot that trails the knot ahead by `(x_diff, y_diff)`,
+    /// or `(0, 0)` if it stays where it is.
+    fn follow(&self, x_diff: i32, y_diff: i32) -> (i32, i32);
+}
+
+impl<F: Fn(i32, i32) -> (i32, i32)> FollowRule for F {
+    fn follow(&self, x_diff: i32, y_diff: i32) -> (i32, i32) {
+        self(x_diff, y_diff)
+    }
+}
+
+/// The puzzle's rule for `slack == 1`: a knot stays while it is within
+/// `slack` steps in both axes, otherwise it takes one (possibly diagonal) step.
+pub struct Chebyshev {
+    pub slack: i32
+}
+
+impl FollowRule for Chebyshev {
+    fn follow(&self, x_diff: i32, y_diff: i32) -> (i32, i32) {
+        if self.slack < x_diff.abs().max(y_diff.abs()) {
+            (x_diff.signum(), y_diff.signum())
+        } else {
+            (0, 0)
+        }
+    }
+}
+
+/// A knot stays while its taxicab distance is at most `slack`, otherwise it
+/// takes one orthogonal step along the axis with the larger gap.
+pub struct Manhattan {
+    pub slack: i32
+}
+
+impl FollowRule for Manhattan {
+    fn follow(&self, x_diff: i32, y_diff: i32) -> (i32, i32) {
+        if x_diff.abs() + y_diff.abs() <= self.slack {
+            (0, 0)
+        } else if y_diff.abs() <= x_diff.abs() {
+            (x_diff.signum(), 0)
+        } else {
+            (0, y_diff.signum())
+        }
+    }
+}
+
+/// The link between two knots stretches up to `max_distance` (euclidean);
+/// beyond that the knot is pulled along until it is back within reach. A
+/// negative `max_distance` acts like 0.
+pub struct MaxStretch {
+    pub max_distance: f64
+}
+
+impl FollowRule for MaxStretch {
+    fn follow(&self, x_diff: i32, y_diff: i32) -> (i32, i32) {
+        let max_distance = self.max_distance.max(0.0);
+        let (mut x_move, mut y_move) = (0, 0);
+        while max_distance < (((x_diff - x_move).pow(2) + (y_diff - y_move).pow(2)) as f64).sqrt() {
+            x_move += (x_diff - x_move).signum();
+            y_move += (y_diff - y_move).signum();
+        }
+        (x_move, y_move)
+    }
+}
+
+/// Positions of every knot after every unit step, starting with the initial state.
+pub struct Trajectory {
+    frames: Vec<Vec<(i32, i32)>>
+}
+
+impl Trajectory {
+    pub fn frames(&self) -> &[Vec<(i32, i32)>] {
+        &self.frames
+    }
+
+    /// Smallest `(min_x, min_y, max_x, max_y)` box containing every knot of every frame.
+    pub fn bounds(&self) -> (i32, i32, i32, i32) {
+        let mut bounds = (0, 0, 0, 0);
+        for &(x, y) in self.frames.iter().flatten() {
+            bounds = (bounds.0.min(x), bounds.1.min(y), bounds.2.max(x), bounds.3.max(y));
+        }
+        bounds
+    }
+
+    /// Draws one frame like the puzzle's diagrams: `H` for the head, `1`..`9`
+    /// (or `T` for a two-knot rope) for the other knots, `s` for the start.
+    /// Lower knots are drawn on top of higher ones.
+    pub fn render_frame(&self, index: usize) -> Option<String> {
+        let frame = self.frames.get(index)?;
+        let (min_x, min_y, max_x, max_y) = self.bounds();
+        let mut output = String::new();
+        for y in (min_y..=max_y).rev() {
+            for x in min_x..=max_x {
+                let symbol = match frame.iter().position(|&p| p == (x, y)) {
+                    Some(0) => 'H',
+                    Some(_) if frame.len() == 2 => 'T',
+                    Some(knot) => char::from_digit(knot as u32 % 36, 36).unwrap(),
+                    None if (x, y) == (0, 0) => 's',
+                    None => '.'
+                };
+                output.push(symbol);
+            }
+            output.push('\n');
+        }
+        Some(output)
+    }
+
+    /// How often `knot` stood on each position, counting every frame.
+    pub fn visit_counts(&self, knot: usize) -> HashMap<(i32, i32), u32> {
+        let mut counts = HashMap::new();
+        for frame in &self.frames {
+            if let Some(&position) = frame.get(knot) {
+                *counts.entry(position).or_insert(0) += 1;
+            }
+        }
+        counts
+    }
+
+    /// SVG heatmap of `visit_counts(knot)` with one `cell_size` square per
+    /// position; more visits give a more opaque square.
+    pub fn to_svg_heatmap(&self, knot: usize, cell_size: u32) -> String {
+        let (min_x, min_y, max_x, max_y) = self.bounds();
+        let width = (max_x - min_x + 1) as u32 * cell_size;
+        let height = (max_y - min_y + 1) as u32 * cell_size;
+        let counts = self.visit_counts(knot);
+        let max_count = counts.values().copied().max().unwrap_or(1);
+        let mut cells: Vec<(&(i32, i32), &u32)> = counts.iter().collect();
+        cells.sort();
+        let mut svg = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">\n", width, height);
+        svg.push_str(&format!("<rect width=\"{}\" height=\"{}\" fill=\"white\"/>\n", width, height));
+        for (&(x, y), &count) in cells {
+            svg.push_str(&format!(
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"red\" fill-opacity=\"{:.3}\"><title>{}</title></rect>\n",
+                (x - min_x) as u32 * cell_size,
+                (max_y - y) as u32 * cell_size,
+                cell_size,
+                cell_size,
+                count as f64 / max_count as f64,
+                count
+            ));
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
 }
 
 impl Walker {
@@ -28,68 +182,146 @@ impl Walker {
             }
             Some(Walker {
                 snake,
-                visited_points: HashMap::from([
-                    ((0,0), true)
-                ])
+                visits: (0..snake_len)
+                    .map(|knot| if knot == snake_len - 1 { Some(HashMap::from([((0,0), 1)])) } else { None })
+                    .collect(),
+                trajectory: None,
+                follow_rule: Box::new(Chebyshev { slack: 1 }),
+                moved: false
             })
         } else {
             None
         }
     }
 
+    /// Replaces the puzzle's follow rule (`Chebyshev { slack: 1 }`).
+    pub fn with_follow_rule<R: FollowRule + 'static>(mut self, rule: R) -> Self {
+        self.follow_rule = Box::new(rule);
+        self
+    }
+
+    /// Also counts the visits of `knots`; by default only the tail's visits
+    /// are counted. `None` if a knot is not part of the rope or the rope has
+    /// already moved, as the earlier visits would be missing.
+    pub fn track_knots(mut self, knots: &[usize]) -> Option<Self> {
+        if self.moved || knots.iter().any(|&knot| self.snake.len() <= knot) {
+            return None;
+        }
+        for &knot in knots {
+            self.visits[knot].get_or_insert_with(|| HashMap::from([((0,0), 1)]));
+        }
+        Some(self)
+    }
+
+    /// Counts the visits of every knot, see `track_knots`.
+    pub fn track_all_knots(self) -> Option<Self> {
+        let knots: Vec<usize> = (0..self.snake.len()).collect();
+        self.track_knots(&knots)
+    }
+
+    /// Starts recording the position of every knot after each step.
+    pub fn record_trajectory(&mut self) {
+        self.trajectory = Some(Trajectory {
+            frames: vec![self.knot_positions()]
+        });
+    }
+
+    pub fn trajectory(&self) -> Option<&Trajectory> {
+        self.trajectory.as_ref()
+    }
+
+    fn knot_positions(&self) -> Vec<(i32, i32)> {
+        self.snake.iter().map(|p| (p.x, p.y)).collect()
+    }
+
+    /// Applies all steps of `m`. Once the rope is rigid in the direction of
+    /// the move, the remaining steps are fast-forwarded in bulk.
     pub fn walk(&mut self, m: Move) {
-        self.walk_rec(m, 0);
+        let mut remaining = m.count;
+        self.moved |= 0 < remaining;
+        while 0 < remaining {
+            if self.trajectory.is_none() && self.is_rigid(m.x, m.y) {
+                self.fast_forward(m.x, m.y, remaining);
+                return;
+            }
+            self.step(m.x, m.y);
+            remaining -= 1;
+        }
+    }
+
+    fn step(&mut self, x: i32, y: i32) {
+        self.walk_rec((x, y), 0);
+        if let Some(trajectory) = &mut self.trajectory {
+            trajectory.frames.push(self.snake.iter().map(|p| (p.x, p.y)).collect());
+        }
     }
 
-    fn walk_rec(&mut self, m: Move, head_index: usize) {
-        self.apply_move(&m, head_index);
+    fn walk_rec(&mut self, m: (i32, i32), head_index: usize) {
+        self.apply_move(m, head_index);
         let tail_index = head_index + 1;
-        if tail_index < self.snake.len(){
-            if self.need_to_move(tail_index) {
-                let (x_diff,y_diff) = get_diffs(&self.snake[head_index], &self.snake[tail_index]);
-                let mut x_move = 0;
-                let mut y_move = 0;
-                if y_diff.abs() < x_diff.abs() {
-                    x_move = calc_move_val(x_diff);
-                    if 0 < y_diff.abs() {
-                        y_move = calc_move_val(y_diff);
-                    }
-                } else {
-                    y_move = calc_move_val(y_diff);
-                    if 0 < x_diff.abs() {
-                        x_move = calc_move_val(x_diff);
-                    }
-                }
-                self.walk_rec(Move {
-                    x: x_move,
-                    y: y_move
-                }, tail_index)
+        if tail_index < self.snake.len() {
+            let (x_diff,y_diff) = get_diffs(&self.snake[head_index], &self.snake[tail_index]);
+            let (x, y) = self.follow_rule.follow(x_diff, y_diff);
+            if (x, y) != (0, 0) {
+                self.walk_rec((x, y), tail_index)
             }
         }
     }
 
-    fn need_to_move(&self, index: usize) -> bool {
-        let head = &self.snake[index - 1];
-        let tail = &self.snake[index];
-        if 1 < (head.x - tail.x).abs() {
-            true
-        } else if 1 < (head.y - tail.y).abs() {
-            true
-        } else {
-            false
+    /// True if one step of `(x, y)` moves every knot by exactly `(x, y)`. The
+    /// gaps between knots are unchanged afterwards, so the same holds for all
+    /// following steps.
+    fn is_rigid(&self, x: i32, y: i32) -> bool {
+        self.snake.windows(2).all(|pair| {
+            let (x_diff, y_diff) = get_diffs(&pair[0], &pair[1]);
+            self.follow_rule.follow(x_diff + x, y_diff + y) == (x, y)
+        })
+    }
+
+    fn fast_forward(&mut self, x: i32, y: i32, count: u32) {
+        for (point, visits) in self.snake.iter_mut().zip(self.visits.iter_mut()) {
+            if let Some(visits) = visits {
+                for i in 1..=count as i32 {
+                    *visits.entry((point.x + i * x, point.y + i * y)).or_insert(0) += 1;
+                }
+            }
+            point.x += count as i32 * x;
+            point.y += count as i32 * y;
         }
     }
 
-    fn apply_move(&mut self, m: &Move, index: usize) {
-        self.snake[index].x += m.x;
-        self.snake[index].y += m.y;
-        if index == self.snake.len() - 1 {
-            self.visited_points.insert((self.snake[index].x, self.snake[index].y), true);
+    fn apply_move(&mut self, m: (i32, i32), index: usize) {
+        self.snake[index].x += m.0;
+        self.snake[index].y += m.1;
+        if let Some(visits) = &mut self.visits[index] {
+            *visits.entry((self.snake[index].x, self.snake[index].y)).or_insert(0) += 1;
         }
     }
 
     pub fn tail_get_number_of_visited(&self) -> usize {
-        self.visited_points.keys().len()
+        self.number_of_visited(self.snake.len() - 1).unwrap()
+    }
+
+    /// Distinct positions visited by `knot`, `None` if it is not tracked. Knots
+    /// never influence the ones ahead of them, so knot `k` is the tail of a
+    /// rope of length `k + 1`.
+    pub fn number_of_visited(&self, knot: usize) -> Option<usize> {
+        self.visits.get(knot)?.as_ref().map(|visits| visits.len())
+    }
+
+    /// Distinct positions visited by every knot, head first.
+    pub fn number_of_visited_per_knot(&self) -> Vec<Option<usize>> {
+        (0..self.snake.len()).map(|knot| self.number_of_visited(knot)).collect()
+    }
+
+    /// Maps a number of arrivals to how many positions `knot` arrived at that
+    /// often, `None` if the knot is not tracked.
+    pub fn visit_histogram(&self, knot: usize) -> Option<BTreeMap<u32, usize>> {
+        let mut histogram = BTreeMap::new();
+        for &count in self.visits.get(knot)?.as_ref()?.values() {
+            *histogram.entry(count).or_insert(0) += 1;
+        }
+        Some(histogram)
     }
 }
 
@@ -97,19 +329,43 @@ fn map_to_move(direction_string: &str) -> Option<Move>{
     match direction_string {
         "U" => Some(Move {
             x: 0,
-            y: 1
+            y: 1,
+            count: 1
         }),
         "D" => Some(Move {
             x: 0,
-            y: -1
+            y: -1,
+            count: 1
         }),
         "L" => Some(Move {
             x: -1,
-            y: 0
+            y: 0,
+            count: 1
         }),
         "R" => Some(Move {
             x: 1,
-            y: 0
+            y: 0,
+            count: 1
+        }),
+        "UL" => Some(Move {
+            x: -1,
+            y: 1,
+            count: 1
+        }),
+        "UR" => Some(Move {
+            x: 1,
+            y: 1,
+            count: 1
+        }),
+        "DL" => Some(Move {
+            x: -1,
+            y: -1,
+            count: 1
+        }),
+        "DR" => Some(Move {
+            x: 1,
+            y: -1,
+            count: 1
         }),
         _ => None
     }
@@ -117,33 +373,35 @@ fn map_to_move(direction_string: &str) -> Option<Move>{
 
 pub fn read_in_file_system(file_path: &str) -> Result<Vec<Move>, &str> {
     if let Ok(lines) = read_lines(file_path) {
-        let mut move_vec = Vec::new();
-        for line in lines {
-            if let Ok(l) = line{
-                let line_vec: Vec<&str> = l.split(' ').collect();
-                if line_vec.len() == 2 {
-                    for _ in 0..line_vec[1].parse().unwrap() {
-                        move_vec.push(map_to_move(line_vec[0]).unwrap());
-                    }
-                }
-            }
-        }
-        Ok(move_vec)
+        parse_moves(lines.map_while(Result::ok))
     } else {
         Err("no valid file")
     }
 }
 
-fn get_diffs(head: &Point, tail: &Point) -> (i32, i32) {
-    (head.x - tail.x, head.y - tail.y)
+/// Parses `<direction> <count>` lines into run-length moves. Counts above
+/// `i32::MAX` are rejected, as the coordinates are `i32`.
+pub fn parse_moves<I, S>(lines: I) -> Result<Vec<Move>, &'static str>
+    where I: Iterator<Item = S>, S: AsRef<str> {
+    let mut move_vec = Vec::new();
+    for l in lines {
+        let line_vec: Vec<&str> = l.as_ref().split(' ').collect();
+        if line_vec.len() == 2 {
+            let count: u32 = line_vec[1].parse().map_err(|_| "invalid count")?;
+            if i32::try_from(count).is_err() {
+                return Err("count too large");
+            }
+            move_vec.push(Move {
+                count,
+                ..map_to_move(line_vec[0]).ok_or("invalid direction")?
+            });
+        }
+    }
+    Ok(move_vec)
 }
 
-fn calc_move_val(val: i32) -> i32 {
-    if 0 < val {
-        1
-    } else {
-        -1
-    }
+fn get_diffs(head: &Point, tail: &Point) -> (i32, i32) {
+    (head.x - tail.x, head.y - tail.y)
 }
 
 fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<fs::File>>>
@@ -154,7 +412,8 @@ fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<fs::File>>>
 
 #[cfg(test)]
 mod tests {
-    use crate::{read_in_file_system, Walker};
+    use std::collections::BTreeMap;
+    use crate::{map_to_move, parse_moves, read_in_file_system, Chebyshev, Manhattan, MaxStretch, Walker};
 
     #[test]
     fn test_puzzle_01(){
@@ -185,4 +444,118 @@ mod tests {
         }
         assert_eq!(walker.tail_get_number_of_visited(), 36)
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_trajectory(){
+        let move_vec = read_in_file_system("input_test").unwrap();
+        let mut walker = Walker::new(2).unwrap();
+        walker.record_trajectory();
+        for m in move_vec {
+            walker.walk(m);
+        }
+        let trajectory = walker.trajectory().unwrap();
+        assert_eq!(trajectory.frames().len(), 25);
+        assert_eq!(trajectory.bounds(), (0, 0, 5, 4));
+        assert_eq!(trajectory.render_frame(4).unwrap(), "......\n......\n......\n......\ns..TH.\n");
+        assert_eq!(trajectory.render_frame(24).unwrap(), "......\n......\n.TH...\n......\ns.....\n");
+        assert_eq!(trajectory.visit_counts(1).len(), 13);
+        assert_eq!(trajectory.visit_counts(1)[&(0, 0)], 2);
+        let svg = trajectory.to_svg_heatmap(1, 10);
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"60\" height=\"50\">"));
+        assert_eq!(svg.matches("<title>").count(), 13);
+    }
+
+    #[test]
+    fn test_follow_rules(){
+        let mut walker = Walker::new(2).unwrap();
+        for _ in 0..3 {
+            walker.walk(map_to_move("UR").unwrap());
+        }
+        assert_eq!(walker.knot_positions(), vec![(3, 3), (2, 2)]);
+        assert_eq!(walker.tail_get_number_of_visited(), 3);
+
+        let mut walker = Walker::new(2).unwrap().with_follow_rule(Manhattan { slack: 1 });
+        walker.walk(map_to_move("UR").unwrap());
+        assert_eq!(walker.knot_positions(), vec![(1, 1), (1, 0)]);
+
+        let mut walker = Walker::new(3).unwrap().with_follow_rule(Chebyshev { slack: 2 });
+        for _ in 0..4 {
+            walker.walk(map_to_move("R").unwrap());
+        }
+        assert_eq!(walker.knot_positions(), vec![(4, 0), (2, 0), (0, 0)]);
+
+        let mut walker = Walker::new(2).unwrap().with_follow_rule(MaxStretch { max_distance: 1.5 });
+        walker.walk(map_to_move("UR").unwrap());
+        walker.walk(map_to_move("R").unwrap());
+        assert_eq!(walker.knot_positions(), vec![(2, 1), (1, 1)]);
+
+        let mut walker = Walker::new(2).unwrap().with_follow_rule(MaxStretch { max_distance: -1.0 });
+        walker.walk(map_to_move("R").unwrap());
+        assert_eq!(walker.knot_positions(), vec![(1, 0), (1, 0)]);
+
+        let mut walker = Walker::new(2).unwrap().with_follow_rule(|x: i32, y: i32| (x, y));
+        walker.walk(map_to_move("DL").unwrap());
+        assert_eq!(walker.knot_positions(), vec![(-1, -1), (-1, -1)]);
+    }
+
+    #[test]
+    fn test_run_length_moves(){
+        let lines = ["R 1000", "U 3", "L 1000", "DR 20", "D 5"];
+        assert_eq!(parse_moves(lines.iter()).unwrap().len(), 5);
+        let mut fast_walker = Walker::new(10).unwrap();
+        // recording a trajectory disables the bulk fast-forward
+        let mut slow_walker = Walker::new(10).unwrap();
+        slow_walker.record_trajectory();
+        for (fast, slow) in parse_moves(lines.iter()).unwrap().into_iter().zip(parse_moves(lines.iter()).unwrap()) {
+            fast_walker.walk(fast);
+            slow_walker.walk(slow);
+            assert_eq!(fast_walker.knot_positions(), slow_walker.knot_positions());
+        }
+        assert_eq!(fast_walker.tail_get_number_of_visited(), slow_walker.tail_get_number_of_visited());
+
+        let mut walker = Walker::new(10).unwrap();
+        walker.walk(parse_moves(["L 100000"].iter()).unwrap().pop().unwrap());
+        assert_eq!(walker.knot_positions()[9], (-99991, 0));
+        assert_eq!(walker.tail_get_number_of_visited(), 99992);
+
+        assert_eq!(parse_moves(["L 2147483647"].iter()).unwrap()[0].count, 2147483647);
+        assert_eq!(parse_moves(["L 2147483648"].iter()).err(), Some("count too large"));
+        assert_eq!(parse_moves(["L -1"].iter()).err(), Some("invalid count"));
+        assert_eq!(parse_moves(["X 1"].iter()).err(), Some("invalid direction"));
+    }
+
+    #[test]
+    fn test_visits_per_knot(){
+        let move_vec = read_in_file_system("input_test").unwrap();
+        let mut walker = Walker::new(10).unwrap().track_all_knots().unwrap();
+        for m in move_vec {
+            walker.walk(m);
+        }
+        assert_eq!(walker.number_of_visited(1), Some(13));
+        assert_eq!(walker.number_of_visited(9), Some(1));
+        assert_eq!(walker.number_of_visited_per_knot(), [21, 13, 7, 4, 3, 2, 1, 1, 1, 1].map(Some));
+        for rope_len in 2..10 {
+            let mut short_walker = Walker::new(rope_len).unwrap();
+            for m in read_in_file_system("input_test").unwrap() {
+                short_walker.walk(m);
+            }
+            assert_eq!(Some(short_walker.tail_get_number_of_visited()), walker.number_of_visited(rope_len - 1));
+        }
+        let histogram = walker.visit_histogram(1).unwrap();
+        assert_eq!(histogram.values().sum::<usize>(), 13);
+        assert_eq!(histogram, BTreeMap::from([(1, 12), (2, 1)]));
+        assert_eq!(walker.visit_histogram(9), Some(BTreeMap::from([(1, 1)])));
+
+        // only the tail is tracked unless asked for
+        let mut walker = Walker::new(10).unwrap().track_knots(&[1]).unwrap();
+        for m in read_in_file_system("input_test").unwrap() {
+            walker.walk(m);
+        }
+        assert_eq!(walker.number_of_visited_per_knot(), vec![None, Some(13), None, None, None, None, None, None, None, Some(1)]);
+        assert_eq!(walker.visit_histogram(2), None);
+        assert_eq!(walker.number_of_visited(10), None);
+        assert_eq!(walker.visit_histogram(10), None);
+        assert!(walker.track_knots(&[2]).is_none());
+        assert!(Walker::new(10).unwrap().track_knots(&[10]).is_none());
+    }
+}
@@ -0,0 +1,362 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Formatter;
+use crate::read_lines;
+
+/// Registers are named `a` to `z`; the puzzle only uses `x`.
+pub const REGISTER_COUNT: usize = 26;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Register(u8);
+
+impl Register {
+    pub const X: Register = Register(b'x' - b'a');
+
+    pub fn from_name(name: &str) -> Option<Register> {
+        match name.as_bytes() {
+            [c @ b'a'..=b'z'] => Some(Register(c - b'a')),
+            _ => None
+        }
+    }
+
+    fn index(&self) -> usize {
+        self.0 as usize
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operand {
+    Register(Register),
+    Value(i32)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    Noop,
+    Addx(i32),
+    Set(Register, Operand),
+    Add(Register, Operand),
+    Mul(Register, Operand),
+    /// jumps hold the index of the target instruction
+    Jmp(usize),
+    /// jump if the register is zero
+    Jz(Register, usize),
+    /// jump if the register is not zero
+    Jnz(Register, usize)
+}
+
+impl Instruction {
+    /// Number of cycles the instruction takes with the default cycle table.
+    pub fn cycles(&self) -> u32 {
+        CycleTable::default().cycles(self)
+    }
+}
+
+/// Cycles each kind of instruction takes to complete.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CycleTable {
+    pub noop: u32,
+    pub addx: u32,
+    pub set: u32,
+    pub add: u32,
+    pub mul: u32,
+    pub jump: u32
+}
+
+impl CycleTable {
+    pub fn cycles(&self, instruction: &Instruction) -> u32 {
+        match instruction {
+            Instruction::Noop => self.noop,
+            Instruction::Addx(_) => self.addx,
+            Instruction::Set(..) => self.set,
+            Instruction::Add(..) => self.add,
+            Instruction::Mul(..) => self.mul,
+            Instruction::Jmp(_) | Instruction::Jz(..) | Instruction::Jnz(..) => self.jump
+        }
+    }
+}
+
+impl Default for CycleTable {
+    fn default() -> Self {
+        CycleTable {
+            noop: 1,
+            addx: 2,
+            set: 1,
+            add: 2,
+            mul: 3,
+            jump: 2
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CpuError {
+    NoValidFile,
+    /// `line` is the 1-based line of the program
+    UnknownInstruction { line: usize, instruction: String },
+    InvalidOperand { line: usize, operand: String },
+    UnknownLabel { line: usize, label: String },
+    DuplicateLabel { line: usize, label: String },
+    /// the machine came back to the same state at `cycle`, so it never halts
+    InfiniteLoop { cycle: u32, program_counter: usize },
+    CycleLimitExceeded { limit: u32 },
+    /// the instruction at `program_counter` overflowed a register in its last cycle
+    Overflow { cycle: u32, program_counter: usize },
+    /// the program halted before the screen was completely drawn
+    ScreenIncomplete { cycles: u32, pixels: usize }
+}
+
+impl std::fmt::Display for CpuError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CpuError::NoValidFile => write!(f, "no valid file"),
+            CpuError::UnknownInstruction { line, instruction } => {
+                write!(f, "unknown instruction {:?} in line {}", instruction, line)
+            }
+            CpuError::InvalidOperand { line, operand } => {
+                write!(f, "invalid operand {:?} in line {}", operand, line)
+            }
+            CpuError::UnknownLabel { line, label } => write!(f, "unknown label {:?} in line {}", label, line),
+            CpuError::DuplicateLabel { line, label } => write!(f, "label {:?} in line {} is already defined", label, line),
+            CpuError::InfiniteLoop { cycle, program_counter } => {
+                write!(f, "infinite loop detected at cycle {} (instruction {})", cycle, program_counter)
+            }
+            CpuError::CycleLimitExceeded { limit } => write!(f, "program did not halt within {} cycles", limit),
+            CpuError::Overflow { cycle, program_counter } => {
+                write!(f, "register overflow at cycle {} (instruction {})", cycle, program_counter)
+            }
+            CpuError::ScreenIncomplete { cycles, pixels } => {
+                write!(f, "program ran {} cycles, but the screen has {} pixels", cycles, pixels)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CpuError {}
+
+/// Register state while a cycle is running.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CycleState {
+    /// 1-based number of the cycle
+    pub cycle: u32,
+    pub x: i32,
+    pub registers: [i32; REGISTER_COUNT]
+}
+
+/// The handheld's CPU: runs a program one clock cycle at a time.
+pub struct Cpu {
+    program: Vec<Instruction>,
+    cycle_table: CycleTable,
+    program_counter: usize,
+    registers: [i32; REGISTER_COUNT],
+    cycle: u32,
+    /// cycles left until the current instruction completes, 0 if none is running
+    remaining_cycles: u32
+}
+
+impl Cpu {
+    pub fn new(program: Vec<Instruction>) -> Self {
+        let mut registers = [0; REGISTER_COUNT];
+        registers[Register::X.index()] = 1;
+        Cpu {
+            program,
+            cycle_table: CycleTable::default(),
+            program_counter: 0,
+            registers,
+            cycle: 0,
+            remaining_cycles: 0
+        }
+    }
+
+    pub fn with_cycle_table(mut self, cycle_table: CycleTable) -> Self {
+        self.cycle_table = cycle_table;
+        self
+    }
+
+    pub fn x(&self) -> i32 {
+        self.register(Register::X)
+    }
+
+    pub fn register(&self, register: Register) -> i32 {
+        self.registers[register.index()]
+    }
+
+    /// Number of cycles completed so far.
+    pub fn cycle(&self) -> u32 {
+        self.cycle
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.remaining_cycles == 0 && self.program.len() <= self.program_counter
+    }
+
+    /// Runs one cycle and returns the state during it, or `None` once the
+    /// program has finished. Instructions take effect at the end of their
+    /// last cycle; if that overflows a register, the registers and the
+    /// program counter are left unchanged and an error is returned.
+    pub fn step(&mut self) -> Result<Option<CycleState>, CpuError> {
+        if self.is_halted() {
+            return Ok(None);
+        }
+        if self.remaining_cycles == 0 {
+            // instructions that take no cycles still need one to be fetched
+            self.remaining_cycles = self.cycle_table.cycles(&self.program[self.program_counter]).max(1);
+        }
+        self.cycle += 1;
+        let state = CycleState {
+            cycle: self.cycle,
+            x: self.x(),
+            registers: self.registers
+        };
+        self.remaining_cycles -= 1;
+        if self.remaining_cycles == 0 {
+            self.program_counter = self.execute(self.program[self.program_counter])?;
+        }
+        Ok(Some(state))
+    }
+
+    /// Applies `instruction` and returns the index of the next one.
+    fn execute(&mut self, instruction: Instruction) -> Result<usize, CpuError> {
+        let next = self.program_counter + 1;
+        match instruction {
+            Instruction::Noop => Ok(next),
+            Instruction::Addx(value) => {
+                self.registers[Register::X.index()] = self.checked(self.x().checked_add(value))?;
+                Ok(next)
+            }
+            Instruction::Set(register, operand) => {
+                self.registers[register.index()] = self.value_of(operand);
+                Ok(next)
+            }
+            Instruction::Add(register, operand) => {
+                self.registers[register.index()] = self.checked(self.register(register).checked_add(self.value_of(operand)))?;
+                Ok(next)
+            }
+            Instruction::Mul(register, operand) => {
+                self.registers[register.index()] = self.checked(self.register(register).checked_mul(self.value_of(operand)))?;
+                Ok(next)
+            }
+            Instruction::Jmp(target) => Ok(target),
+            Instruction::Jz(register, target) => Ok(if self.register(register) == 0 { target } else { next }),
+            Instruction::Jnz(register, target) => Ok(if self.register(register) != 0 { target } else { next })
+        }
+    }
+
+    fn checked(&self, result: Option<i32>) -> Result<i32, CpuError> {
+        result.ok_or(CpuError::Overflow { cycle: self.cycle, program_counter: self.program_counter })
+    }
+
+    fn value_of(&self, operand: Operand) -> i32 {
+        match operand {
+            Operand::Register(register) => self.register(register),
+            Operand::Value(value) => value
+        }
+    }
+
+    /// Runs the program to the end, calling `during` for every cycle.
+    pub fn run<F: FnMut(CycleState)>(&mut self, mut during: F) -> Result<(), CpuError> {
+        while let Some(state) = self.step()? {
+            during(state);
+        }
+        Ok(())
+    }
+
+    /// Like `run`, but fails instead of running forever: an error is returned
+    /// when the machine returns to an earlier state between two instructions,
+    /// or when it has not halted after `max_cycles`.
+    pub fn run_guarded<F: FnMut(CycleState)>(&mut self, max_cycles: u32, mut during: F) -> Result<(), CpuError> {
+        let mut seen_states = HashSet::new();
+        loop {
+            if self.remaining_cycles == 0 && !self.is_halted() &&
+                !seen_states.insert((self.program_counter, self.registers)) {
+                return Err(CpuError::InfiniteLoop { cycle: self.cycle, program_counter: self.program_counter });
+            }
+            if max_cycles <= self.cycle && !self.is_halted() {
+                return Err(CpuError::CycleLimitExceeded { limit: max_cycles });
+            }
+            match self.step()? {
+                Some(state) => during(state),
+                None => return Ok(())
+            }
+        }
+    }
+
+    /// Runs until cycle `cycle_number` and returns the state during it.
+    pub fn run_until(&mut self, cycle_number: u32) -> Result<Option<CycleState>, CpuError> {
+        while let Some(state) = self.step()? {
+            if state.cycle == cycle_number {
+                return Ok(Some(state));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Parses a program. Lines ending in `:` define labels for the following
+/// instruction; jumps take a label or a relative offset like `+2` or `-3`.
+pub fn parse_program<I, S>(lines: I) -> Result<Vec<Instruction>, CpuError>
+    where I: Iterator<Item = S>, S: AsRef<str> {
+    let mut labels = HashMap::new();
+    let mut instruction_lines = Vec::new();
+    for (line_number, l) in lines.enumerate() {
+        let line = line_number + 1;
+        let l = l.as_ref().trim();
+        if l.is_empty() {
+            continue
+        }
+        if let Some(label) = l.strip_suffix(':') {
+            if labels.insert(label.to_string(), instruction_lines.len()).is_some() {
+                return Err(CpuError::DuplicateLabel { line, label: label.to_string() });
+            }
+        } else {
+            instruction_lines.push((line, l.to_string()));
+        }
+    }
+    let mut program = Vec::new();
+    for (index, (line, l)) in instruction_lines.iter().enumerate() {
+        program.push(parse_instruction(l, *line, index, &labels)?);
+    }
+    Ok(program)
+}
+
+fn parse_instruction(l: &str, line: usize, index: usize, labels: &HashMap<String, usize>) -> Result<Instruction, CpuError> {
+    let register = |name: &str| Register::from_name(name)
+        .ok_or(CpuError::InvalidOperand { line, operand: name.to_string() });
+    let operand = |token: &str| match Register::from_name(token) {
+        Some(register) => Ok(Operand::Register(register)),
+        None => token.parse().map(Operand::Value)
+            .map_err(|_| CpuError::InvalidOperand { line, operand: token.to_string() })
+    };
+    let target = |token: &str| {
+        if token.starts_with(['+', '-']) {
+            token.parse::<isize>().ok()
+                .and_then(|offset| index.checked_add_signed(offset))
+                .ok_or(CpuError::InvalidOperand { line, operand: token.to_string() })
+        } else {
+            labels.get(token).copied()
+                .ok_or(CpuError::UnknownLabel { line, label: token.to_string() })
+        }
+    };
+    let line_vec: Vec<&str> = l.split_whitespace().collect();
+    match line_vec.as_slice() {
+        ["noop"] => Ok(Instruction::Noop),
+        ["addx", value] => value
+            .parse()
+            .map(Instruction::Addx)
+            .map_err(|_| CpuError::InvalidOperand { line, operand: value.to_string() }),
+        ["set", r, o] => Ok(Instruction::Set(register(r)?, operand(o)?)),
+        ["add", r, o] => Ok(Instruction::Add(register(r)?, operand(o)?)),
+        ["mul", r, o] => Ok(Instruction::Mul(register(r)?, operand(o)?)),
+        ["jmp", t] => Ok(Instruction::Jmp(target(t)?)),
+        ["jz", r, t] => Ok(Instruction::Jz(register(r)?, target(t)?)),
+        ["jnz", r, t] => Ok(Instruction::Jnz(register(r)?, target(t)?)),
+        _ => Err(CpuError::UnknownInstruction { line, instruction: l.to_string() })
+    }
+}
+
+pub fn read_program(file_path: &str) -> Result<Vec<Instruction>, CpuError> {
+    if let Ok(lines) = read_lines(file_path) {
+        parse_program(lines.map_while(Result::ok))
+    } else {
+        Err(CpuError::NoValidFile)
+    }
+}
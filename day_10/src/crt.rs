@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+use std::fmt::Formatter;
+use crate::is_pixel_in_sprite;
+
+const GLYPH_WIDTH: usize = 4;
+const GLYPH_HEIGHT: usize = 6;
+
+/// The 4×6 capital letters drawn by the handheld, one row per string.
+const GLYPHS: [(char, [&str; GLYPH_HEIGHT]); 16] = [
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"])
+];
+
+/// Geometry and timing of a display driven by register X.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DisplayConfig {
+    pub width: usize,
+    pub height: usize,
+    /// pixels covered by the sprite, centered on X (even widths extend to the right)
+    pub sprite_width: i32,
+    /// cycle during which the top left pixel is drawn
+    pub first_cycle: i32,
+    pub cycles_per_pixel: i32,
+    /// if true the horizontal position restarts at 0 on every row, otherwise
+    /// pixels are numbered continuously across the whole screen
+    pub wrap_rows: bool
+}
+
+impl Default for DisplayConfig {
+    /// The handheld's 40×6 screen with a 3 pixel sprite.
+    fn default() -> Self {
+        DisplayConfig {
+            width: 40,
+            height: 6,
+            sprite_width: 3,
+            first_cycle: 1,
+            cycles_per_pixel: 1,
+            wrap_rows: true
+        }
+    }
+}
+
+/// Framebuffer of the handheld's CRT; `true` is a lit pixel.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Crt {
+    width: usize,
+    height: usize,
+    pixels: Vec<bool>
+}
+
+impl Crt {
+    pub fn new(width: usize, height: usize) -> Self {
+        Crt {
+            width,
+            height,
+            pixels: vec![false; width * height]
+        }
+    }
+
+    /// Draws the screen from the register X value during each cycle: the CRT
+    /// draws one pixel per cycle, row by row, and lights it if the sprite
+    /// covers it. `None` if the program ran fewer cycles than there are pixels.
+    pub fn from_register_states(width: usize, height: usize, register_map: &HashMap<i32, i32>) -> Option<Self> {
+        Crt::render(&DisplayConfig { width, height, ..DisplayConfig::default() }, register_map)
+    }
+
+    /// Like `from_register_states`, for any display geometry and timing.
+    pub fn render(config: &DisplayConfig, register_map: &HashMap<i32, i32>) -> Option<Self> {
+        let mut crt = Crt::new(config.width, config.height);
+        for row in 0..config.height {
+            for col in 0..config.width {
+                let pixel = (row * config.width + col) as i32;
+                let cycle_number = config.first_cycle + pixel * config.cycles_per_pixel;
+                let position = if config.wrap_rows { col as i32 } else { pixel };
+                let sprite_x = *register_map.get(&cycle_number)?;
+                crt.set(row, col, is_pixel_in_sprite(position, sprite_x, config.sprite_width));
+            }
+        }
+        Some(crt)
+    }
+
+    /// Reads a screen drawn with `#` and `.`, one line per row.
+    pub fn from_text(text: &str) -> Option<Self> {
+        let rows: Vec<&str> = text.lines().filter(|l| !l.is_empty()).collect();
+        let width = rows.first().map_or(0, |row| row.len());
+        let mut crt = Crt::new(width, rows.len());
+        for (row, l) in rows.iter().enumerate() {
+            if l.len() != width {
+                return None;
+            }
+            for (col, c) in l.chars().enumerate() {
+                match c {
+                    '#' => crt.set(row, col, true),
+                    '.' => {}
+                    _ => return None
+                }
+            }
+        }
+        Some(crt)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> bool {
+        self.pixels[row * self.width + col]
+    }
+
+    pub fn set(&mut self, row: usize, col: usize, lit: bool) {
+        self.pixels[row * self.width + col] = lit;
+    }
+
+    /// Plain (ASCII) PBM image, 1 is a lit pixel.
+    pub fn to_pbm(&self) -> String {
+        let mut pbm = format!("P1\n{} {}\n", self.width, self.height);
+        for row in 0..self.height {
+            let line: Vec<&str> = (0..self.width)
+                .map(|col| if self.get(row, col) { "1" } else { "0" })
+                .collect();
+            pbm.push_str(&line.join(" "));
+            pbm.push('\n');
+        }
+        pbm
+    }
+
+    /// Grayscale PNG with every pixel drawn as a `scale`×`scale` square,
+    /// lit pixels white on black. A `scale` of 0 is a format error.
+    pub fn to_png(&self, scale: usize) -> Result<Vec<u8>, png::EncodingError> {
+        let mut data = Vec::with_capacity(self.pixels.len() * scale * scale);
+        for row in 0..self.height * scale {
+            for col in 0..self.width * scale {
+                data.push(if self.get(row / scale, col / scale) { 255 } else { 0 });
+            }
+        }
+        let mut bytes = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, (self.width * scale) as u32, (self.height * scale) as u32);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&data)?;
+        Ok(bytes)
+    }
+
+    /// Reads the capital letters on screen. Letters are 4 pixels wide with one
+    /// blank column between them; unknown shapes are returned as `?`.
+    pub fn ocr(&self) -> String {
+        let mut text = String::new();
+        if self.height != GLYPH_HEIGHT {
+            return text;
+        }
+        for start in (0..self.width).step_by(GLYPH_WIDTH + 1) {
+            if self.width < start + GLYPH_WIDTH {
+                break
+            }
+            let glyph = GLYPHS.iter().find(|(_, rows)| {
+                rows.iter().enumerate().all(|(row, pattern)| {
+                    pattern.chars().enumerate().all(|(col, c)| (c == '#') == self.get(row, start + col))
+                })
+            });
+            text.push(glyph.map_or('?', |(letter, _)| *letter));
+        }
+        text
+    }
+}
+
+impl std::fmt::Display for Crt {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for row in 0..self.height {
+            for col in 0..self.width {
+                write!(f, "{}", if self.get(row, col) { '#' } else { '.' })?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
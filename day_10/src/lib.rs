@@ -1,65 +1,53 @@
+mod cpu;
+mod crt;
+
 use std::fs;
 use std::collections::HashMap;
 use std::io;
 use std::io::BufRead;
 use std::path::Path;
 
-pub fn read_in_file_system(file_path: &str) -> Result<HashMap<i32, i32>, &str> {
-    if let Ok(lines) = read_lines(file_path) {
-        let mut cycle_number = 1;
-        let mut register_value = 1;
-        let mut register_states = HashMap::new();
-        for line in lines {
-            if let Ok(l) = line{
-                let line_vec: Vec<&str> = l.split(' ').collect();
-                if line_vec.len() == 1 {
-                    // noop
-                    register_states.insert(cycle_number, register_value);
-                    cycle_number += 1;
-                } else if line_vec.len() == 2 {
-                    // addx
-                    let val: i32 = line_vec[1].parse().unwrap();
-                    register_states.insert(cycle_number, register_value);
-                    cycle_number += 1;
-                    register_states.insert(cycle_number, register_value);
-                    cycle_number += 1;
-                    register_value += val;
-                }
-            }
-        }
-        Ok(register_states)
-    } else {
-        Err("no valid file")
-    }
+pub use crt::{Crt, DisplayConfig};
+pub use cpu::{parse_program, read_program, Cpu, CpuError, CycleState, CycleTable, Instruction, Operand, Register, REGISTER_COUNT};
+
+/// Register X during every cycle of the program, keyed by the 1-based cycle number.
+pub fn read_in_file_system(file_path: &str) -> Result<HashMap<i32, i32>, CpuError> {
+    let mut register_states = HashMap::new();
+    Cpu::new(read_program(file_path)?).run(|state| {
+        register_states.insert(state.cycle as i32, state.x);
+    })?;
+    Ok(register_states)
 }
 
 pub fn signal_strength_at_cycle(cycle_number: i32, register_map: &HashMap<i32, i32>) -> Option<i32> {
-    match register_map.get(&cycle_number) {
-        Some(val) => Some(cycle_number * val),
-        None => None
-    }
+    register_map.get(&cycle_number).map(|val| cycle_number * val)
 }
 
 pub fn get_signal_strength_sum(cycle_numbers: Vec<i32>, register_map: &HashMap<i32, i32>) -> i32 {
     let mut sum = 0;
     for i in cycle_numbers {
-        sum += signal_strength_at_cycle(i, &register_map).unwrap();
+        sum += signal_strength_at_cycle(i, register_map).unwrap();
     }
     sum
 }
 
 pub fn is_cycle_in_sprite(cycle_number: &i32, cycle_pos: &i32, register_map: &HashMap<i32, i32>) -> Option<bool> {
-    if let Some(s) = register_map.get(cycle_number) {
-        let sprite = vec![s-1, *s, s+1];
-        //println!("Sprite: {:?}, Pos: {}", sprite, cycle_pos);
-        if sprite.contains(cycle_pos) {
-            Some(true)
-        } else {
-            Some(false)
-        }
-    } else {
-        None
-    }
+    register_map.get(cycle_number).map(|s| is_pixel_in_sprite(*cycle_pos, *s, 3))
+}
+
+/// Runs the program and returns what the 40×6 screen shows as text.
+pub fn read_screen_text(file_path: &str) -> Result<String, CpuError> {
+    let register_states = read_in_file_system(file_path)?;
+    Crt::from_register_states(40, 6, &register_states)
+        .map(|crt| crt.ocr())
+        .ok_or(CpuError::ScreenIncomplete { cycles: register_states.len() as u32, pixels: 40 * 6 })
+}
+
+/// Whether a sprite of `sprite_width` pixels centered on `sprite_x` covers
+/// `position`; even widths extend one more pixel to the right.
+pub fn is_pixel_in_sprite(position: i32, sprite_x: i32, sprite_width: i32) -> bool {
+    let left = sprite_x - (sprite_width - 1) / 2;
+    left <= position && position < left + sprite_width
 }
 
 fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<fs::File>>>
@@ -70,7 +58,8 @@ fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<fs::File>>>
 
 #[cfg(test)]
 mod tests {
-    use crate::{get_signal_strength_sum, read_in_file_system, signal_strength_at_cycle};
+    use std::collections::HashMap;
+    use crate::{get_signal_strength_sum, is_pixel_in_sprite, Crt, DisplayConfig, parse_program, read_in_file_system, read_program, read_screen_text, signal_strength_at_cycle, Cpu, CpuError, CycleTable, Instruction, Operand, Register};
 
     #[test]
     fn test_single_states(){
@@ -90,4 +79,127 @@ mod tests {
         let register_states = read_in_file_system("input_test").unwrap();
         assert_eq!(get_signal_strength_sum(vec![20, 60, 100, 140, 180, 220], &register_states), 13140);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_cpu() {
+        let program = parse_program(["noop", "addx 3", "addx -5"].iter()).unwrap();
+        assert_eq!(program, vec![Instruction::Noop, Instruction::Addx(3), Instruction::Addx(-5)]);
+        let mut cpu = Cpu::new(program);
+        let mut states = Vec::new();
+        cpu.run(|state| states.push(state.x)).unwrap();
+        assert_eq!(states, vec![1, 1, 1, 4, 4]);
+        assert_eq!((cpu.cycle(), cpu.x(), cpu.is_halted()), (5, -1, true));
+        assert_eq!(cpu.step(), Ok(None));
+
+        let mut cpu = Cpu::new(read_program("input_test").unwrap());
+        assert_eq!(cpu.run_until(20).unwrap().map(|state| state.x), Some(21));
+        assert_eq!(cpu.run_until(60).unwrap().map(|state| (state.cycle, state.x)), Some((60, 19)));
+
+        assert_eq!(parse_program(["noop", "", "mulx 2"].iter()),
+                   Err(CpuError::UnknownInstruction { line: 3, instruction: "mulx 2".to_string() }));
+        assert_eq!(parse_program(["addx two"].iter()),
+                   Err(CpuError::InvalidOperand { line: 1, operand: "two".to_string() }));
+    }
+
+    #[test]
+    fn test_extended_instructions() {
+        // x = 3 * 4 by repeated addition, counting down in y
+        let program = parse_program([
+            "set y 4",
+            "set x 0",
+            "loop:",
+            "add x 3",
+            "add y -1",
+            "jnz y loop",
+            "mul x x",
+            "jz z +2",
+            "set x 0",
+        ].iter()).unwrap();
+        assert_eq!(program[4], Instruction::Jnz(Register::from_name("y").unwrap(), 2));
+        assert_eq!(program[5], Instruction::Mul(Register::X, Operand::Register(Register::X)));
+        assert_eq!(program[6], Instruction::Jz(Register::from_name("z").unwrap(), 8));
+        let mut cpu = Cpu::new(program.clone());
+        cpu.run_guarded(1000, |_| {}).unwrap();
+        assert_eq!((cpu.x(), cpu.register(Register::from_name("y").unwrap())), (144, 0));
+        assert_eq!(cpu.cycle(), 1 + 1 + 4 * (2 + 2 + 2) + 3 + 2);
+
+        let table = CycleTable { add: 1, jump: 1, mul: 1, ..CycleTable::default() };
+        let mut cpu = Cpu::new(program).with_cycle_table(table);
+        cpu.run(|_| {}).unwrap();
+        assert_eq!((cpu.x(), cpu.cycle()), (144, 1 + 1 + 4 * 3 + 1 + 1));
+
+        let mut cpu = Cpu::new(parse_program(["start:", "noop", "jmp start"].iter()).unwrap());
+        assert_eq!(cpu.run_guarded(1000, |_| {}), Err(CpuError::InfiniteLoop { cycle: 3, program_counter: 0 }));
+        let mut cpu = Cpu::new(parse_program(["start:", "add y 1", "jmp start"].iter()).unwrap());
+        assert_eq!(cpu.run_guarded(1000, |_| {}), Err(CpuError::CycleLimitExceeded { limit: 1000 }));
+        // squaring forever overflows long before the cycle limit
+        let mut cpu = Cpu::new(parse_program(["set x 2", "l:", "mul x x", "jmp l"].iter()).unwrap());
+        assert_eq!(cpu.run_guarded(1000, |_| {}), Err(CpuError::Overflow { cycle: 1 + 4 * 5 + 3, program_counter: 1 }));
+        assert_eq!(cpu.x(), 65536);
+        let mut cpu = Cpu::new(parse_program(["addx 2147483646", "addx 1"].iter()).unwrap());
+        assert_eq!(cpu.run(|_| {}), Err(CpuError::Overflow { cycle: 4, program_counter: 1 }));
+        assert_eq!(parse_program(["jmp nowhere"].iter()),
+                   Err(CpuError::UnknownLabel { line: 1, label: "nowhere".to_string() }));
+    }
+
+    #[test]
+    fn test_crt() {
+        let register_states = read_in_file_system("input_test").unwrap();
+        let crt = Crt::from_register_states(40, 6, &register_states).unwrap();
+        assert_eq!(crt.to_string().lines().next(), Some("##..##..##..##..##..##..##..##..##..##.."));
+        assert_eq!(crt.to_string().lines().last(), Some("#######.......#######.......#######....."));
+        assert!(Crt::from_register_states(40, 7, &register_states).is_none());
+
+        let crt = Crt::from_text("\
+#..#.####.###..
+#..#.#....#..#.
+####.###..#..#.
+#..#.#....###..
+#..#.#....#.#..
+#..#.####.#..#.
+").unwrap();
+        assert_eq!(crt.ocr(), "HER");
+        assert!(crt.to_pbm().starts_with("P1\n15 6\n1 0 0 1 0 1 1 1 1 0 1 1 1 0 0\n"));
+        let png = crt.to_png(2).unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        assert!(matches!(crt.to_png(0), Err(png::EncodingError::Format(_))));
+        let mut broken = crt.clone();
+        broken.set(0, 0, false);
+        assert_eq!(broken.ocr(), "?ER");
+    }
+
+    #[test]
+    fn test_read_screen_text() {
+        assert_eq!(read_screen_text("input"), Ok("PGHFGLUG".to_string()));
+        // the test program draws stripes, not letters
+        assert_eq!(read_screen_text("input_test"), Ok("????????".to_string()));
+
+        let path = std::env::temp_dir().join(format!("day_10_short_{}", std::process::id()));
+        std::fs::write(&path, "noop\naddx 3\n").unwrap();
+        let result = read_screen_text(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(result, Err(CpuError::ScreenIncomplete { cycles: 3, pixels: 240 }));
+        assert_eq!(read_screen_text("missing"), Err(CpuError::NoValidFile));
+    }
+
+    #[test]
+    fn test_display_config() {
+        assert!(is_pixel_in_sprite(0, -1, 3));
+        assert!(!is_pixel_in_sprite(0, -2, 3));
+        assert!(is_pixel_in_sprite(2, 1, 2) && !is_pixel_in_sprite(0, 1, 2));
+        assert!(is_pixel_in_sprite(3, 1, 5) && !is_pixel_in_sprite(4, 1, 5));
+
+        // sprite parked at x = -1, so only its right edge is on screen
+        let register_states: HashMap<i32, i32> = (1..=20).map(|cycle| (cycle, -1)).collect();
+        let config = DisplayConfig { width: 4, height: 2, ..DisplayConfig::default() };
+        assert_eq!(Crt::render(&config, &register_states).unwrap().to_string(), "#...\n#...\n");
+        let config = DisplayConfig { sprite_width: 5, ..config };
+        assert_eq!(Crt::render(&config, &register_states).unwrap().to_string(), "##..\n##..\n");
+        let config = DisplayConfig { wrap_rows: false, ..config };
+        assert_eq!(Crt::render(&config, &register_states).unwrap().to_string(), "##..\n....\n");
+        let config = DisplayConfig { cycles_per_pixel: 3, ..config };
+        assert!(Crt::render(&config, &register_states).is_none());
+        let config = DisplayConfig { cycles_per_pixel: 2, first_cycle: 2, ..config };
+        assert_eq!(Crt::render(&config, &register_states).unwrap().to_string(), "##..\n....\n");
+    }
+}
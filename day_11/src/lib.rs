@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
 use std::fs;
 use std::io;
@@ -5,42 +6,169 @@ use std::io::BufRead;
 use std::path::Path;
 use regex::Regex;
 use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
 
+/// `new = old <operator> <operand>`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Operation {
+    pub operator: Operator,
+    pub operand: Operand
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Operator {
+    Add,
+    Multiply
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Operand {
+    Old,
+    Value(u64)
+}
+
+impl Operation {
+    /// The new worry level, or `None` if it does not fit into a `u64`.
+    pub fn apply(&self, old: u64) -> Option<u64> {
+        let value = match self.operand {
+            Operand::Old => old,
+            Operand::Value(value) => value
+        };
+        match self.operator {
+            Operator::Add => old.checked_add(value),
+            Operator::Multiply => old.checked_mul(value)
+        }
+    }
+
+    /// The new worry level modulo `modulus`. Computed in `u128`, so it cannot
+    /// overflow for any `old` and operand. Panics if `modulus` is 0.
+    pub fn apply_modulo(&self, old: u64, modulus: u64) -> u64 {
+        let value = match self.operand {
+            Operand::Old => old,
+            Operand::Value(value) => value
+        } as u128 % modulus as u128;
+        let old = old as u128 % modulus as u128;
+        let new = match self.operator {
+            Operator::Add => old + value,
+            Operator::Multiply => old * value
+        };
+        (new % modulus as u128) as u64
+    }
+}
+
+impl Display for Operation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let operator = match self.operator {
+            Operator::Add => "+",
+            Operator::Multiply => "*"
+        };
+        match self.operand {
+            Operand::Old => write!(f, "new = old {} old", operator),
+            Operand::Value(value) => write!(f, "new = old {} {}", operator, value)
+        }
+    }
+}
+
+/// Throws to `if_true` when the worry level is divisible by `divisible_by`,
+/// otherwise to `if_false`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Test {
+    pub divisible_by: u64,
+    pub if_true: usize,
+    pub if_false: usize
+}
+
+impl Test {
+    pub fn throw_to(&self, worry_level: u64) -> usize {
+        if worry_level.is_multiple_of(self.divisible_by) {
+            self.if_true
+        } else {
+            self.if_false
+        }
+    }
+}
+
+/// How worry levels shrink after an item has been inspected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WorryRelief {
+    /// part 1: the level is divided by the value (rounded down)
+    DivideBy(u64),
+    /// part 2: only the remainder modulo the value is kept
+    Modulo(u64)
+}
+
+/// A worry level grew beyond `u64` while a monkey inspected an item.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OverflowError {
+    pub monkey: usize,
+    pub worry_level: u64,
+    pub operation: Operation
+}
+
+impl Display for OverflowError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "worry level overflow: monkey {} applied \"{}\" to {}", self.monkey, self.operation, self.worry_level)
+    }
+}
+
+impl std::error::Error for OverflowError {}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Monkey {
+    pub id: usize,
     items: Vec<u64>,
-    operation: Box<dyn Fn(u64) -> u64> ,
-    throw_to: Box<dyn Fn (&u64) -> usize>,
+    operation: Operation,
+    test: Test,
     pub inspect_counter: u64,
-    worry_calc: Box<dyn Fn(u64) -> u64>
+    worry_relief: WorryRelief
 }
 
 impl Monkey {
-    fn new(items: Vec<u64>, operation: Box<dyn Fn(u64) -> u64>, throw_to: Box<dyn Fn (&u64) -> usize>) -> Self {
+    pub fn new(id: usize, items: Vec<u64>, operation: Operation, test: Test) -> Self {
         Monkey {
+            id,
             items,
             operation,
-            throw_to,
+            test,
             inspect_counter: 0,
-            worry_calc: Box::new(|x| x.saturating_div(3))
+            worry_relief: WorryRelief::DivideBy(3)
         }
     }
 
-    pub fn inspect_items(&mut self) {
-        for i in 0..self.items.len(){
-            self.items[i] = (self.operation)(self.items[i]);
-            self.inspect_counter += 1;
+    pub fn items(&self) -> &[u64] {
+        &self.items
+    }
+
+    pub fn operation(&self) -> Operation {
+        self.operation
+    }
+
+    pub fn test(&self) -> Test {
+        self.test
+    }
+
+    /// Worry level of an item after this monkey inspected it and the worry relief.
+    pub fn inspect_item(&self, worry_level: u64) -> Result<u64, OverflowError> {
+        match self.worry_relief {
+            WorryRelief::DivideBy(divisor) => self.operation.apply(worry_level)
+                .map(|new| new / divisor)
+                .ok_or(OverflowError { monkey: self.id, worry_level, operation: self.operation }),
+            WorryRelief::Modulo(modulus) => Ok(self.operation.apply_modulo(worry_level, modulus))
         }
     }
 
+    /// Inspects all items. On overflow the items stay untouched.
+    pub fn inspect_items(&mut self) -> Result<(), OverflowError> {
+        let items = self.items.iter().map(|&item| self.inspect_item(item)).collect::<Result<Vec<_>, _>>()?;
+        self.inspect_counter += items.len() as u64;
+        self.items = items;
+        Ok(())
+    }
+
     pub fn throw_items(&mut self) -> Vec<(u64, usize)> {
         let mut throw_vec = Vec::new();
-        loop {
-            if let Some(item) = self.items.pop() {
-                let item_to_throw = (self.worry_calc)(item);
-                throw_vec.push((item_to_throw, (self.throw_to)(&item_to_throw)));
-            } else {
-                break;
-            }
+        while let Some(item_to_throw) = self.items.pop() {
+            throw_vec.push((item_to_throw, self.test.throw_to(item_to_throw)));
         }
         throw_vec
     }
@@ -49,50 +177,264 @@ impl Monkey {
         self.items.push(item);
     }
 
-    pub fn set_worry_calc(&mut self, f: Box<dyn Fn(u64) -> u64>) {
-        self.worry_calc = f;
+    /// Fails for `DivideBy(0)` and `Modulo(0)`, keeping the current relief.
+    pub fn set_worry_relief(&mut self, worry_relief: WorryRelief) -> Result<(), &'static str> {
+        match worry_relief {
+            WorryRelief::DivideBy(0) => Err("worry levels can not be divided by 0"),
+            WorryRelief::Modulo(0) => Err("worry levels can not be taken modulo 0"),
+            _ => {
+                self.worry_relief = worry_relief;
+                Ok(())
+            }
+        }
     }
 }
 
+/// Writes the monkey in the format of the puzzle's notes.
 impl Display for Monkey {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Items: {:?}\nInspect Counter: {}", self.items, self.inspect_counter)
+        let items: Vec<String> = self.items.iter().map(|item| item.to_string()).collect();
+        writeln!(f, "Monkey {}:", self.id)?;
+        writeln!(f, "  Starting items: {}", items.join(", "))?;
+        writeln!(f, "  Operation: {}", self.operation)?;
+        writeln!(f, "  Test: divisible by {}", self.test.divisible_by)?;
+        writeln!(f, "    If true: throw to monkey {}", self.test.if_true)?;
+        write!(f, "    If false: throw to monkey {}", self.test.if_false)
     }
 }
 
-pub fn read_in_file_system(file_path: &str) -> Result<(Vec<Monkey>, u64), &str> {
-    if let Ok(mut lines) = read_lines(file_path) {
-        lazy_static! {
-            static ref MONKEY_REGEX: Regex = Regex::new(r"Monkey \d+:").unwrap();
+/// A group of monkeys playing keep away, indexed by their ids.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Troop {
+    monkeys: Vec<Monkey>,
+    rounds: u64,
+    /// `(round, items inspected by each monkey)` for every round run one by
+    /// one; rounds skipped with `fast_forward` are not part of it
+    history: Vec<(u64, Vec<u64>)>
+}
+
+impl Troop {
+    pub fn new(monkeys: Vec<Monkey>) -> Self {
+        Troop {
+            monkeys,
+            rounds: 0,
+            history: Vec::new()
         }
-        let mut monkey_vec = vec![];
-        let mut ssn = 1;
-        loop {
-            if let Some(line) = lines.next() {
-                if let Ok(l) = line {
-                    if MONKEY_REGEX.is_match(&l) {
-                        let items_string = lines.next().unwrap().unwrap();
-                        let items = parse_items(items_string);
-                        let operation_string = lines.next().unwrap().unwrap();
-                        let operation = parse_operation(operation_string).unwrap();
-                        let test_line_1 = lines.next().unwrap().unwrap();
-                        let test_line_2 = lines.next().unwrap().unwrap();
-                        let test_line_3 = lines.next().unwrap().unwrap();
-                        let (throw_to, divisor) = parse_test(test_line_1, test_line_2, test_line_3);
-                        ssn *= divisor;
-                        monkey_vec.push(Monkey::new(items, operation, throw_to));
+    }
+
+    pub fn monkeys(&self) -> &[Monkey] {
+        &self.monkeys
+    }
+
+    pub fn rounds(&self) -> u64 {
+        self.rounds
+    }
+
+    /// Sets the relief of every monkey, see `Monkey::set_worry_relief`.
+    pub fn set_worry_relief(&mut self, worry_relief: WorryRelief) -> Result<(), &'static str> {
+        for monkey in self.monkeys.iter_mut() {
+            monkey.set_worry_relief(worry_relief)?;
+        }
+        Ok(())
+    }
+
+    /// Every monkey in turn inspects and throws all of its items. The round is
+    /// played on a copy, so on overflow the troop stays as it was before it.
+    pub fn run_round(&mut self) -> Result<(), OverflowError> {
+        let mut monkeys = self.monkeys.clone();
+        for i in 0..monkeys.len() {
+            monkeys[i].inspect_items()?;
+            let throw_vec = monkeys[i].throw_items();
+            for (item, throw_target) in throw_vec {
+                monkeys[throw_target].catch_item(item);
+            }
+        }
+        self.rounds += 1;
+        let counts = monkeys.iter().zip(&self.monkeys).map(|(m, before)| m.inspect_counter - before.inspect_counter).collect();
+        self.monkeys = monkeys;
+        self.history.push((self.rounds, counts));
+        Ok(())
+    }
+
+    pub fn run_rounds(&mut self, n: usize) -> Result<(), OverflowError> {
+        self.run_rounds_with(n, |_, _| {})
+    }
+
+    /// Runs `n` rounds and calls `on_round(round, monkeys)` after each of them,
+    /// with `round` counted from 1 since the troop was created. On overflow
+    /// the rounds before the failing one stay played.
+    pub fn run_rounds_with<F: FnMut(u64, &[Monkey])>(&mut self, n: usize, mut on_round: F) -> Result<(), OverflowError> {
+        for _ in 0..n {
+            self.run_round()?;
+            on_round(self.rounds(), &self.monkeys);
+        }
+        Ok(())
+    }
+
+    /// Product of the inspection counts of the `top_k` most active monkeys.
+    pub fn monkey_business(&self, top_k: usize) -> u64 {
+        let mut vals: Vec<u64> = self.monkeys.iter().map(|m| m.inspect_counter).collect();
+        vals.sort_unstable_by(|a, b| b.cmp(a));
+        vals.iter().take(top_k).product()
+    }
+
+    /// Advances the troop by `rounds` rounds without playing them one by one.
+    ///
+    /// Items never influence each other, so every item is followed on its own.
+    /// Its state at the start of a round (holder and worry level) comes from a
+    /// finite set as long as worry levels are kept small by the worry relief,
+    /// so the states eventually repeat; once they do, the inspections of the
+    /// remaining rounds are extrapolated from the cycle.
+    pub fn fast_forward(&mut self, rounds: u64) -> Result<(), OverflowError> {
+        let mut counts = vec![0u64; self.monkeys.len()];
+        let mut final_items = vec![Vec::new(); self.monkeys.len()];
+        for monkey in 0..self.monkeys.len() {
+            for &item in self.monkeys[monkey].items() {
+                let (holder, worry_level) = self.fast_forward_item(monkey, item, rounds, &mut counts)?;
+                final_items[holder].push(worry_level);
+            }
+        }
+        for ((monkey, items), count) in self.monkeys.iter_mut().zip(final_items).zip(counts) {
+            monkey.items = items;
+            monkey.inspect_counter += count;
+        }
+        self.rounds += rounds;
+        Ok(())
+    }
+
+    /// Follows one item for `rounds` rounds, adds its inspections to `counts`
+    /// and returns where it ends up.
+    fn fast_forward_item(&self, monkey: usize, item: u64, rounds: u64, counts: &mut [u64]) -> Result<(usize, u64), OverflowError> {
+        let mut seen: HashMap<(usize, u64), usize> = HashMap::new();
+        // state at the start of each simulated round and who inspected the item in it
+        let mut states = vec![(monkey, item)];
+        let mut inspections: Vec<Vec<usize>> = Vec::new();
+        let mut state = (monkey, item);
+        while (inspections.len() as u64) < rounds {
+            if let Some(&cycle_start) = seen.get(&state) {
+                let cycle_len = (inspections.len() - cycle_start) as u64;
+                let remaining = rounds - inspections.len() as u64;
+                for inspected in &inspections[cycle_start..] {
+                    for &m in inspected {
+                        counts[m] += remaining / cycle_len;
+                    }
+                }
+                let rest = (remaining % cycle_len) as usize;
+                for inspected in &inspections[cycle_start..cycle_start + rest] {
+                    for &m in inspected {
+                        counts[m] += 1;
                     }
                 }
-            } else {
-                break
+                return Ok(states[cycle_start + rest]);
+            }
+            seen.insert(state, inspections.len());
+            let mut inspected = Vec::new();
+            state = self.item_round(state, &mut inspected)?;
+            for &m in &inspected {
+                counts[m] += 1;
+            }
+            inspections.push(inspected);
+            states.push(state);
+        }
+        Ok(state)
+    }
+
+    /// Plays one round for a single item, recording which monkeys inspect it.
+    fn item_round(&self, (mut holder, mut worry_level): (usize, u64), inspected: &mut Vec<usize>) -> Result<(usize, u64), OverflowError> {
+        loop {
+            let monkey = &self.monkeys[holder];
+            inspected.push(holder);
+            worry_level = monkey.inspect_item(worry_level)?;
+            let target = monkey.test.throw_to(worry_level);
+            // monkeys after the current one still get their turn in this round
+            if target <= holder {
+                return Ok((target, worry_level));
+            }
+            holder = target;
+        }
+    }
+
+    pub fn inspection_history(&self) -> &[(u64, Vec<u64>)] {
+        &self.history
+    }
+
+    /// The inspection history as CSV: one row per recorded round, one column per monkey.
+    pub fn history_csv(&self) -> String {
+        let mut csv = String::from("round");
+        for monkey in &self.monkeys {
+            csv.push_str(&format!(",monkey_{}", monkey.id));
+        }
+        csv.push('\n');
+        for (round, counts) in &self.history {
+            csv.push_str(&round.to_string());
+            for count in counts {
+                csv.push_str(&format!(",{}", count));
             }
+            csv.push('\n');
         }
-        Ok((monkey_vec, ssn))
+        csv
+    }
+}
+
+/// Writes all monkeys back out as notes, separated by blank lines.
+pub fn to_notes(monkey_vec: &[Monkey]) -> String {
+    let blocks: Vec<String> = monkey_vec.iter().map(|monkey| monkey.to_string()).collect();
+    blocks.join("\n\n") + "\n"
+}
+
+pub fn read_in_file_system(file_path: &str) -> Result<(Vec<Monkey>, u64), &str> {
+    if let Ok(lines) = read_lines(file_path) {
+        parse_notes(lines.map_while(Result::ok))
     } else {
         Err("no valid file")
     }
 }
 
+/// Parses the notes into monkeys and the least common multiple of all
+/// divisors. Keeping worry levels modulo this number leaves every test's
+/// outcome unchanged, whether the divisors are prime or not. Fails if a
+/// monkey throws to a monkey that is not in the notes.
+pub fn parse_notes<I, S>(lines: I) -> Result<(Vec<Monkey>, u64), &'static str>
+    where I: Iterator<Item = S>, S: AsRef<str> {
+    lazy_static! {
+        static ref MONKEY_REGEX: Regex = Regex::new(r"Monkey (\d+):").unwrap();
+    }
+    let mut lines = lines.map(|l| l.as_ref().to_string());
+    let mut next_line = || lines.next().ok_or("unexpected end of notes");
+    let mut monkey_vec = vec![];
+    let mut lcm = 1;
+    while let Ok(l) = next_line() {
+        if let Some(cap) = MONKEY_REGEX.captures(&l) {
+            let id = cap[1].parse().map_err(|_| "invalid monkey id")?;
+            let items = parse_items(next_line()?);
+            let operation = parse_operation(next_line()?).ok_or("invalid operation")?;
+            let test = parse_test(next_line()?, next_line()?, next_line()?).ok_or("invalid test")?;
+            if test.divisible_by == 0 {
+                return Err("monkeys can not test for divisibility by 0");
+            }
+            lcm = least_common_multiple(lcm, test.divisible_by).ok_or("least common multiple of the divisors overflows")?;
+            monkey_vec.push(Monkey::new(id, items, operation, test));
+        }
+    }
+    let monkey_count = monkey_vec.len();
+    if monkey_vec.iter().any(|monkey| monkey_count <= monkey.test.if_true || monkey_count <= monkey.test.if_false) {
+        return Err("monkeys can only throw to monkeys in the notes");
+    }
+    Ok((monkey_vec, lcm))
+}
+
+fn greatest_common_divisor(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        greatest_common_divisor(b, a % b)
+    }
+}
+
+fn least_common_multiple(a: u64, b: u64) -> Option<u64> {
+    (a / greatest_common_divisor(a, b)).checked_mul(b)
+}
+
 fn parse_items(line: String) -> Vec<u64> {
     lazy_static! {
         static ref ITEM_REGEX: Regex = Regex::new(r"(\d+)(, )?").unwrap();
@@ -105,53 +447,35 @@ fn parse_items(line: String) -> Vec<u64> {
     item_vec
 }
 
-fn parse_operation(operation_string: String) -> Option<Box<dyn Fn(u64) -> u64>> {
+fn parse_operation(operation_string: String) -> Option<Operation> {
     lazy_static! {
         static ref OPERATION_REGEX: Regex = Regex::new(r"new = old (.) (old|\d+)").unwrap();
     }
-    let cap = OPERATION_REGEX.captures_iter(&operation_string).next().unwrap();
-    let operator = &cap[1];
-    let value = &cap[2];
-    if operator == "+" {
-        if value == "old" {
-            Some(Box::new(|x| x + x))
-        } else {
-            let num_value: u64 = value.parse().unwrap();
-            Some(Box::new(move |x| x + num_value))
-        }
-    } else if operator == "*" {
-        if value == "old" {
-            Some(Box::new(|x| x * x))
-        } else {
-            let num_value: u64 = value.parse().unwrap();
-            Some(Box::new(move |x| x * num_value))
-        }
-    } else {
-        None
-    }
+    let cap = OPERATION_REGEX.captures(&operation_string)?;
+    let operator = match &cap[1] {
+        "+" => Operator::Add,
+        "*" => Operator::Multiply,
+        _ => return None
+    };
+    let operand = match &cap[2] {
+        "old" => Operand::Old,
+        value => Operand::Value(value.parse().ok()?)
+    };
+    Some(Operation { operator, operand })
 }
 
-fn parse_test(test_line_1: String, test_line_2: String, test_line_3: String) -> (Box<dyn Fn (&u64) -> usize>, u64) {
+fn parse_test(test_line_1: String, test_line_2: String, test_line_3: String) -> Option<Test> {
     lazy_static! {
         static ref DIVISIBLE_BY: Regex = Regex::new(r"Test: divisible by (\d+)").unwrap();
         static ref TRUE_MONKEY_ID: Regex = Regex::new(r"If true: throw to monkey (\d+)").unwrap();
         static ref FALSE_MONKEY_ID: Regex = Regex::new(r"If false: throw to monkey (\d+)").unwrap();
     }
 
-    let c_divisible_by = DIVISIBLE_BY.captures_iter(&test_line_1).next().unwrap();
-    let divisible_by: u64 = c_divisible_by[1].parse().unwrap();
-    let c_true_monkey_id = TRUE_MONKEY_ID.captures_iter(&test_line_2).next().unwrap();
-    let true_monkey_id: usize = c_true_monkey_id[1].parse().unwrap();
-    let c_false_monkey_id = FALSE_MONKEY_ID.captures_iter(&test_line_3).next().unwrap();
-    let false_monkey_id= c_false_monkey_id[1].parse().unwrap();
-
-    (Box::new(move |x| {
-        if x % divisible_by == 0 {
-            true_monkey_id
-        } else {
-            false_monkey_id
-        }
-    }), divisible_by)
+    Some(Test {
+        divisible_by: DIVISIBLE_BY.captures(&test_line_1)?[1].parse().ok()?,
+        if_true: TRUE_MONKEY_ID.captures(&test_line_2)?[1].parse().ok()?,
+        if_false: FALSE_MONKEY_ID.captures(&test_line_3)?[1].parse().ok()?
+    })
 }
 
 fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<fs::File>>>
@@ -162,48 +486,135 @@ fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<fs::File>>>
 
 #[cfg(test)]
 mod tests {
-    use crate::read_in_file_system;
+    use crate::{parse_notes, read_in_file_system, to_notes, Monkey, Operand, Operation, Operator, Troop, WorryRelief};
 
     #[test]
     fn test_single_states() {
-        let (mut monkey_vec, _) = read_in_file_system("input_test").unwrap();
-        for _ in 0..20 {
-            for i in 0..monkey_vec.len() {
-                monkey_vec[i].inspect_items();
-                let throw_vec = monkey_vec[i].throw_items();
-                for (item, throw_target) in throw_vec {
-                    monkey_vec[throw_target].catch_item(item);
-                }
-            }
-        }
+        let (monkey_vec, _) = read_in_file_system("input_test").unwrap();
+        let mut troop = Troop::new(monkey_vec);
+        troop.run_rounds(20).unwrap();
+        let monkey_vec = troop.monkeys();
         assert_eq!(monkey_vec[0].inspect_counter, 101);
         assert_eq!(monkey_vec[1].inspect_counter, 95);
         assert_eq!(monkey_vec[2].inspect_counter, 7);
         assert_eq!(monkey_vec[3].inspect_counter, 105);
+        assert_eq!(troop.monkey_business(2), 10605);
     }
 
     #[test]
     fn test_single_states_2() {
-        // to do: kleinster gemeinsamer Nenner
-        let (mut monkey_vec, ssn) = read_in_file_system("input_test").unwrap();
-        println!("{}", ssn);
-        for i in 0..monkey_vec.len() {
-            let ssn_temp = ssn;
-            monkey_vec[i].set_worry_calc(Box::new(move |x| x % ssn_temp));
-        }
-
-        for _ in 0..20 {
-            for i in 0..monkey_vec.len() {
-                monkey_vec[i].inspect_items();
-                let throw_vec = monkey_vec[i].throw_items();
-                for (item, throw_target) in throw_vec {
-                    monkey_vec[throw_target].catch_item(item);
-                }
-            }
-        }
+        let (monkey_vec, ssn) = read_in_file_system("input_test").unwrap();
+        let mut troop = Troop::new(monkey_vec);
+        troop.set_worry_relief(WorryRelief::Modulo(ssn)).unwrap();
+        troop.run_rounds(20).unwrap();
+        let monkey_vec = troop.monkeys();
         assert_eq!(monkey_vec[0].inspect_counter, 99);
         assert_eq!(monkey_vec[1].inspect_counter, 97);
         assert_eq!(monkey_vec[2].inspect_counter, 8);
         assert_eq!(monkey_vec[3].inspect_counter, 103);
+        troop.run_rounds(10000 - 20).unwrap();
+        assert_eq!(troop.monkey_business(2), 2713310158);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_round_hooks() {
+        let (monkey_vec, _) = read_in_file_system("input_test").unwrap();
+        let mut troop = Troop::new(monkey_vec);
+        let mut rounds_seen = Vec::new();
+        troop.run_rounds_with(2, |round, monkeys| rounds_seen.push((round, monkeys[0].inspect_counter))).unwrap();
+        assert_eq!(rounds_seen, vec![(1, 2), (2, 6)]);
+        assert_eq!(troop.inspection_history(), &[(1, vec![2, 4, 3, 5]), (2, vec![4, 6, 1, 5])]);
+        assert_eq!(troop.history_csv(), "round,monkey_0,monkey_1,monkey_2,monkey_3\n1,2,4,3,5\n2,4,6,1,5\n");
+        assert_eq!(troop.monkey_business(1), 10);
+        assert_eq!(troop.monkey_business(0), 1);
+    }
+
+    #[test]
+    fn test_notes_round_trip() {
+        let (monkey_vec, ssn) = read_in_file_system("input_test").unwrap();
+        assert_eq!(ssn, 23 * 19 * 13 * 17);
+        assert_eq!(monkey_vec[2].operation(), Operation { operator: Operator::Multiply, operand: Operand::Old });
+        assert_eq!(monkey_vec[0].test().throw_to(46), 2);
+        let notes = to_notes(&monkey_vec);
+        assert_eq!(notes, std::fs::read_to_string("input_test").unwrap().trim_end().to_string() + "\n");
+        let (reparsed, _) = parse_notes(notes.lines()).unwrap();
+        assert_eq!(reparsed, monkey_vec);
+
+        let json = serde_json::to_string(&monkey_vec).unwrap();
+        let restored: Vec<Monkey> = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, monkey_vec);
+        assert!(parse_notes(["Monkey 0:", "  Starting items: 1"].iter()).is_err());
+        let oversized_id = notes.replacen("Monkey 0:", "Monkey 99999999999999999999999:", 1);
+        assert_eq!(parse_notes(oversized_id.lines()), Err("invalid monkey id"));
+        let unknown_target = notes.replacen("throw to monkey 2", "throw to monkey 4", 1);
+        assert_eq!(parse_notes(unknown_target.lines()), Err("monkeys can only throw to monkeys in the notes"));
+    }
+
+    #[test]
+    fn test_fast_forward() {
+        let (monkey_vec, ssn) = read_in_file_system("input_test").unwrap();
+        let mut simulated = Troop::new(monkey_vec);
+        simulated.set_worry_relief(WorryRelief::Modulo(ssn)).unwrap();
+        let mut fast = simulated.clone();
+        simulated.run_rounds(10000).unwrap();
+        fast.fast_forward(9000).unwrap();
+        fast.fast_forward(1000).unwrap();
+        assert_eq!(fast.rounds(), 10000);
+        assert_eq!(fast.monkey_business(2), 2713310158);
+        for (a, b) in fast.monkeys().iter().zip(simulated.monkeys()) {
+            let mut items_a = a.items().to_vec();
+            let mut items_b = b.items().to_vec();
+            items_a.sort();
+            items_b.sort();
+            assert_eq!((a.inspect_counter, items_a), (b.inspect_counter, items_b));
+        }
+        fast.run_round().unwrap();
+        assert_eq!(fast.inspection_history(), &[(10001, fast.inspection_history()[0].1.clone())]);
+        fast.fast_forward(1_000_000_000).unwrap();
+        assert_eq!(fast.rounds(), 1_000_010_001);
+    }
+
+    #[test]
+    fn test_lcm_and_overflow() {
+        let notes = "\
+Monkey 0:
+  Starting items: 5, 9
+  Operation: new = old * old
+  Test: divisible by 4
+    If true: throw to monkey 1
+    If false: throw to monkey 1
+
+Monkey 1:
+  Starting items: 7
+  Operation: new = old + 3
+  Test: divisible by 6
+    If true: throw to monkey 0
+    If false: throw to monkey 0
+";
+        let (monkey_vec, lcm) = parse_notes(notes.lines()).unwrap();
+        assert_eq!(lcm, 12);
+        let mut with_lcm = Troop::new(monkey_vec.clone());
+        with_lcm.set_worry_relief(WorryRelief::Modulo(lcm)).unwrap();
+        let mut with_product = Troop::new(monkey_vec.clone());
+        with_product.set_worry_relief(WorryRelief::Modulo(4 * 6)).unwrap();
+        assert!(with_lcm.set_worry_relief(WorryRelief::Modulo(0)).is_err());
+        assert!(with_lcm.set_worry_relief(WorryRelief::DivideBy(0)).is_err());
+        with_lcm.run_rounds(100).unwrap();
+        with_product.run_rounds(100).unwrap();
+        assert_eq!(with_lcm.inspection_history(), with_product.inspection_history());
+
+        // squaring without a modulus outgrows u64 after a few rounds
+        let mut troop = Troop::new(monkey_vec);
+        let error = troop.run_rounds(100).unwrap_err();
+        let before = troop.clone();
+        assert_eq!(troop.run_round(), Err(error));
+        assert_eq!(troop, before);
+        assert_eq!(error.monkey, 0);
+        assert!(error.worry_level > u32::MAX as u64);
+        assert!(error.to_string().starts_with("worry level overflow: monkey 0 applied \"new = old * old\" to "));
+
+        let big = Operation { operator: Operator::Multiply, operand: Operand::Value(u64::MAX - 1) };
+        assert_eq!(big.apply(3), None);
+        assert_eq!(big.apply_modulo(u64::MAX - 2, u64::MAX), 2);
+    }
+}
@@ -1,137 +1,185 @@
-use std::cell::{Ref, RefCell};
+mod render;
+mod search;
+
 use std::fs;
-use std::collections::{HashMap, VecDeque};
 use std::io;
 use std::io::BufRead;
 use std::path::Path;
 
-struct Node {
-    x: i32,
-    y: i32,
-    elevation: i32,
-    cost: u32,
-    explored: bool,
-    is_end: bool
+pub use render::{render_arrows, to_png, to_ppm};
+pub use search::{a_star, bfs, dijkstra, distance_map, DistanceMap, Route};
+
+/// A point on the heightmap as `(x, y)`, `x` being the column.
+pub type Point = (i32, i32);
+
+/// The heightmap from the puzzle input. It is never modified by a search, so
+/// any number of searches can run on the same map.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HeightMap {
+    width: i32,
+    height: i32,
+    elevations: Vec<i32>,
+    start: Point,
+    end: Point
 }
 
-impl Node {
-    fn set_explored(&mut self) {
-        self.explored = true;
+impl HeightMap {
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
     }
-    fn set_cost(&mut self, cost: u32) {
-        self.cost = cost;
+
+    /// The square marked `S`.
+    pub fn start(&self) -> Point {
+        self.start
     }
-}
 
-fn is_end(node: &RefCell<Node>) -> bool {
-    node.borrow().is_end
-}
+    /// The square marked `E`.
+    pub fn end(&self) -> Point {
+        self.end
+    }
+
+    pub fn contains(&self, (x, y): Point) -> bool {
+        0 <= x && x < self.width && 0 <= y && y < self.height
+    }
+
+    /// Elevation from 1 (`a`) to 26 (`z`), `None` outside of the map.
+    pub fn elevation(&self, point: Point) -> Option<i32> {
+        self.index(point).map(|i| self.elevations[i])
+    }
+
+    /// All points, row by row.
+    pub fn points(&self) -> impl Iterator<Item = Point> + '_ {
+        (0..self.height).flat_map(move |y| (0..self.width).map(move |x| (x, y)))
+    }
 
-fn is_a(node: &RefCell<Node>) -> bool {
-    node.borrow().elevation == 1
+    /// All points at the given elevation, row by row.
+    pub fn points_at(&self, elevation: i32) -> impl Iterator<Item = Point> + '_ {
+        self.points().filter(move |&point| self.elevation(point) == Some(elevation))
+    }
+
+    /// Neighbours of `point` that the rules allow to step on.
+    pub fn neighbours<'a>(&'a self, point: Point, rules: &'a MovementRules) -> impl Iterator<Item = Point> + 'a {
+        let (x, y) = point;
+        let elevation = self.elevation(point);
+        let directions: &[Point] = if rules.diagonal { &COMPASS } else { &COMPASS[..4] };
+        directions.iter()
+            .map(move |(dx, dy)| (x + dx, y + dy))
+            .filter(move |&neighbour| match (elevation, self.elevation(neighbour)) {
+                (Some(from), Some(to)) => rules.can_step(from, to),
+                _ => false
+            })
+    }
+
+    pub(crate) fn index(&self, point: Point) -> Option<usize> {
+        if self.contains(point) {
+            Some((point.1 * self.width + point.0) as usize)
+        } else {
+            None
+        }
+    }
 }
 
-fn can_step_up(node_elevation: i32, neighbour_elevation: i32) -> bool {
-    neighbour_elevation <= node_elevation + 1
+/// Orthogonal steps first, then diagonal ones.
+const COMPASS: [Point; 8] = [(-1, 0), (1, 0), (0, -1), (0, 1), (-1, -1), (1, -1), (-1, 1), (1, 1)];
+
+/// Extra cost of a step per unit of height it climbs or descends.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StepCost {
+    pub per_ascent: u32,
+    pub per_descent: u32
 }
 
-fn can_step_down(node_elevation: i32, neighbour_elevation: i32) -> bool {
-     node_elevation <= neighbour_elevation + 1
+/// Which steps are allowed and what they cost.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MovementRules {
+    /// highest a single step may climb
+    pub max_ascent: i32,
+    /// deepest a single step may descend
+    pub max_descent: i32,
+    /// if true the diagonal neighbours can be entered as well
+    pub diagonal: bool,
+    /// cost on top of 1 per step; only searches using costs (`dijkstra`,
+    /// `a_star`) take it into account
+    pub step_cost: StepCost
 }
 
-struct NodeSearch {
-    map: HashMap<(i32, i32), RefCell<Node>>,
-    stop: fn (&RefCell<Node>) -> bool,
-    can_step_on_neighbour: fn (i32, i32) -> bool
+impl Default for MovementRules {
+    /// The puzzle's rules: climb at most one, descend any height, no diagonal
+    /// steps, every step costs 1.
+    fn default() -> Self {
+        MovementRules {
+            max_ascent: 1,
+            max_descent: i32::MAX,
+            diagonal: false,
+            step_cost: StepCost::default()
+        }
+    }
 }
 
-impl NodeSearch {
-    fn new(map: HashMap<(i32, i32), RefCell<Node>>) -> Self {
-        NodeSearch {
-            map,
-            stop: is_end,
-            can_step_on_neighbour: can_step_up
+impl MovementRules {
+    pub fn can_step(&self, from_elevation: i32, to_elevation: i32) -> bool {
+        let ascent = to_elevation - from_elevation;
+        ascent <= self.max_ascent && -ascent <= self.max_descent
+    }
+
+    pub fn cost(&self, from_elevation: i32, to_elevation: i32) -> u32 {
+        let ascent = to_elevation - from_elevation;
+        1 + self.step_cost.per_ascent * ascent.max(0) as u32 + self.step_cost.per_descent * (-ascent).max(0) as u32
+    }
+
+    /// The rules for walking every step backwards, e.g. to search from the
+    /// goal towards possible starts.
+    pub fn reversed(&self) -> Self {
+        MovementRules {
+            max_ascent: self.max_descent,
+            max_descent: self.max_ascent,
+            diagonal: self.diagonal,
+            step_cost: StepCost { per_ascent: self.step_cost.per_descent, per_descent: self.step_cost.per_ascent }
         }
     }
+}
 
-    fn bfs(&mut self, point: (i32, i32)) -> Option<u32> {
-        let mut queue= VecDeque::new();
-        let start_node = self.map.get(&point).unwrap();
-        start_node.borrow_mut().set_explored();
-        queue.push_front(start_node);
-        while !queue.is_empty() {
-            let node = queue.pop_back().unwrap();
-            if (self.stop)(node) {
-                return Some(node.borrow().cost)
-            }
-            let unvisited_neighbours = self.get_unvisited_neighbours(&node.borrow());
-            for neighbour_node in unvisited_neighbours {
-                neighbour_node.borrow_mut().set_explored();
-                neighbour_node.borrow_mut().set_cost(node.borrow().cost + 1);
-                queue.push_front(neighbour_node);
-            }
+pub fn parse_height_map<I, S>(lines: I) -> Result<HeightMap, &'static str>
+    where I: Iterator<Item = S>, S: AsRef<str> {
+    let mut elevations = Vec::new();
+    let mut width = None;
+    let mut height = 0;
+    let mut start_point = None;
+    let mut end_point = None;
+    for l in lines {
+        let l = l.as_ref();
+        if l.is_empty() {
+            continue
         }
-        None
-    }
-
-    fn get_unvisited_neighbours(&self, node: &Ref<Node>) -> Vec<&RefCell<Node>> {
-        let mut neighbour_vec = vec![];
-        let neighbour_points = vec![
-            (node.x - 1, node.y),
-            (node.x + 1, node.y),
-            (node.x, node.y - 1),
-            (node.x, node.y + 1)
-        ];
-        for neighbour_point in neighbour_points {
-            if self.is_unvisited_neighbour(&node, &neighbour_point){
-                neighbour_vec.push(self.map.get(&neighbour_point).unwrap());
+        for (column, c) in l.chars().enumerate() {
+            match c {
+                'S' => start_point = Some((column as i32, height)),
+                'E' => end_point = Some((column as i32, height)),
+                _ => {}
             }
+            elevations.push(map_to_elevation(&c).ok_or("invalid square")?);
         }
-        neighbour_vec
-    }
-
-    fn is_unvisited_neighbour(&self, node: &Ref<Node>, neighbour_point: &(i32, i32)) -> bool{
-        match self.map.get(neighbour_point) {
-            Some(neighbour_node) => {
-                !neighbour_node.borrow().explored && (self.can_step_on_neighbour)(node.elevation, neighbour_node.borrow().elevation)
-            },
-            None => false
+        if *width.get_or_insert(l.chars().count()) != l.chars().count() {
+            return Err("rows differ in length");
         }
+        height += 1;
     }
+    Ok(HeightMap {
+        width: width.unwrap_or(0) as i32,
+        height,
+        elevations,
+        start: start_point.ok_or("no start square")?,
+        end: end_point.ok_or("no end square")?
+    })
 }
 
-fn read_in_file_system(file_path: &str) -> Result<(HashMap<(i32, i32), RefCell<Node>>, (i32, i32), (i32, i32)), &str> {
+pub fn read_in_file_system(file_path: &str) -> Result<HeightMap, &str> {
     if let Ok(lines) = read_lines(file_path) {
-        let mut map = HashMap::new();
-        let mut row = 0;
-        let mut start_point = None;
-        let mut end_point = None;
-        for line in lines {
-            if let Ok(l) = line {
-                let mut column = 0;
-                for c in l.chars() {
-                    let mut is_end = false;
-                    if c == 'E' {
-                        is_end = true;
-                        end_point = Some((column, row));
-                    }
-                    map.insert((column, row), RefCell::new(Node {
-                        x: column,
-                        y: row,
-                        elevation: map_to_elevation(&c).unwrap(),
-                        cost: 0,
-                        explored: false,
-                        is_end
-                    }));
-                    if c == 'S' {
-                        start_point = Some((column, row));
-                    }
-                    column += 1;
-                }
-                row += 1;
-            }
-        }
-        Ok((map, start_point.unwrap(), end_point.unwrap()))
+        parse_height_map(lines.map_while(Result::ok))
     } else {
         Err("no valid file")
     }
@@ -145,55 +193,32 @@ fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<fs::File>>>
 
 fn map_to_elevation(c: &char) -> Option<i32> {
     match c {
-        'a' => Some(1),
-        'b' => Some(2),
-        'c' => Some(3),
-        'd' => Some(4),
-        'e' => Some(5),
-        'f' => Some(6),
-        'g' => Some(7),
-        'h' => Some(8),
-        'i' => Some(9),
-        'j' => Some(10),
-        'k' => Some(11),
-        'l' => Some(12),
-        'm' => Some(13),
-        'n' => Some(14),
-        'o' => Some(15),
-        'p' => Some(16),
-        'q' => Some(17),
-        'r' => Some(18),
-        's' => Some(19),
-        't' => Some(20),
-        'u' => Some(21),
-        'v' => Some(22),
-        'w' => Some(23),
-        'x' => Some(24),
-        'y' => Some(25),
-        'z' => Some(26),
+        'a'..='z' => Some(*c as i32 - 'a' as i32 + 1),
         'S' => Some(1),
         'E' => Some(26),
         _ => None
     }
 }
 
+/// Shortest route from `S` to `E`.
+pub fn shortest_route(map: &HeightMap) -> Option<Route> {
+    let end = map.end();
+    bfs(map, map.start(), |point| point == end, &MovementRules::default())
+}
+
 pub fn solve_routine(file_path: &str) -> Option<u32> {
-    let (map, start_point, _) = read_in_file_system(file_path).unwrap();
-    let mut node_search = NodeSearch::new(map);
-    node_search.bfs(start_point)
+    let map = read_in_file_system(file_path).unwrap();
+    shortest_route(&map).map(|route| route.cost)
 }
 
 pub fn solve_routine_2(file_path: &str) -> Option<u32> {
-    let (map, _, end_point) = read_in_file_system(file_path).unwrap();
-    let mut node_search = NodeSearch::new(map);
-    node_search.stop = is_a;
-    node_search.can_step_on_neighbour = can_step_down;
-    node_search.bfs(end_point)
+    let map = read_in_file_system(file_path).unwrap();
+    distance_map(&map, map.points_at(1), &MovementRules::default()).distance(map.end())
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{solve_routine, solve_routine_2};
+    use crate::{a_star, bfs, dijkstra, distance_map, read_in_file_system, render_arrows, shortest_route, solve_routine, solve_routine_2, to_png, to_ppm, MovementRules, StepCost};
 
     #[test]
     fn test_puzzle_1() {
@@ -205,4 +230,110 @@ mod tests {
         assert_eq!(solve_routine_2("input_test").unwrap(), 29)
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_search_algorithms() {
+        let map = read_in_file_system("input_test").unwrap();
+        let (start, end) = (map.start(), map.end());
+        let rules = MovementRules::default();
+        let route = bfs(&map, start, |point| point == end, &rules).unwrap();
+        assert_eq!(route.cost, 31);
+        assert_eq!(route.points.len(), 32);
+        assert_eq!(route.points.first(), Some(&start));
+        assert_eq!(route.points.last(), Some(&end));
+        for step in route.points.windows(2) {
+            let ((x1, y1), (x2, y2)) = (step[0], step[1]);
+            assert_eq!((x1 - x2).abs() + (y1 - y2).abs(), 1);
+            assert!(rules.can_step(map.elevation(step[0]).unwrap(), map.elevation(step[1]).unwrap()));
+        }
+        // the map is untouched, so searching again gives the same route
+        assert_eq!(bfs(&map, start, |point| point == end, &rules), Some(route));
+
+        assert_eq!(dijkstra(&map, start, |point| point == end, &rules).unwrap().cost, 31);
+        assert_eq!(a_star(&map, start, end, &rules).unwrap().cost, 31);
+
+        // climbing costs extra: every route has to climb from a to z, so 25 more
+        let climbing = MovementRules { step_cost: StepCost { per_ascent: 1, per_descent: 0 }, ..rules };
+        let weighted = dijkstra(&map, start, |point| point == end, &climbing).unwrap();
+        assert_eq!(weighted.cost, 31 + 25);
+        assert_eq!(a_star(&map, start, end, &climbing).unwrap().cost, weighted.cost);
+
+        assert_eq!(bfs(&map, start, |_| false, &rules), None);
+    }
+
+    #[test]
+    fn test_distance_map() {
+        let map = read_in_file_system("input_test").unwrap();
+        let from_start = distance_map(&map, [map.start()], &MovementRules::default());
+        assert_eq!(from_start.distance(map.end()), Some(31));
+        assert_eq!(from_start.distance(map.start()), Some(0));
+        assert_eq!(from_start.distance((-1, 0)), None);
+        assert_eq!(from_start.distance((8, 0)), None);
+        let mut nearby: Vec<_> = from_start.within(1).collect();
+        nearby.sort();
+        assert_eq!(nearby, vec![(0, 0), (0, 1), (1, 0)]);
+        assert_eq!(from_start.reachable().count() as i32, map.width() * map.height());
+
+        // distance from every square to E
+        let to_end = distance_map(&map, [map.end()], &MovementRules::default().reversed());
+        assert_eq!(to_end.distance(map.start()), Some(31));
+        assert_eq!(map.points_at(1).filter_map(|point| to_end.distance(point)).min(), Some(29));
+        assert_eq!(distance_map(&map, map.points_at(1), &MovementRules::default()).distance(map.end()), Some(29));
+    }
+
+    #[test]
+    fn test_render_route() {
+        let map = read_in_file_system("input_test").unwrap();
+        let route = shortest_route(&map).unwrap();
+        let arrows = render_arrows(&map, &route);
+        assert_eq!(arrows.lines().count(), 5);
+        assert_eq!(arrows.chars().filter(|c| "><v^".contains(*c)).count(), 31);
+        assert_eq!(arrows.lines().nth(2).unwrap().chars().nth(5), Some('E'));
+        assert!(arrows.lines().all(|l| l.len() == 8));
+
+        let ppm = to_ppm(&map, Some(&route), 2).unwrap();
+        let mut lines = ppm.lines();
+        assert_eq!(lines.next(), Some("P3"));
+        assert_eq!(lines.next(), Some("16 10"));
+        assert_eq!(lines.next(), Some("255"));
+        // S is on the route, so drawn red
+        assert!(lines.next().unwrap().starts_with("220 20 20 220 20 20 "));
+        assert_eq!(ppm.lines().count(), 3 + 10);
+        assert!(!to_ppm(&map, None, 1).unwrap().contains("220 20 20"));
+
+        let png = to_png(&map, Some(&route), 3).unwrap();
+        assert_eq!(&png[1..4], b"PNG");
+    }
+
+    #[test]
+    fn test_render_zero_scale() {
+        let map = read_in_file_system("input_test").unwrap();
+        assert_eq!(to_ppm(&map, None, 0), None);
+        assert!(matches!(to_png(&map, None, 0), Err(png::EncodingError::Format(_))));
+    }
+
+    #[test]
+    fn test_movement_rules() {
+        let map = read_in_file_system("input_test").unwrap();
+        let (start, end) = (map.start(), map.end());
+        let rules = MovementRules::default();
+        assert!(rules.can_step(3, 4) && !rules.can_step(3, 5) && rules.can_step(26, 1));
+        assert_eq!(rules.reversed().reversed(), rules);
+
+        let steps = |rules: &MovementRules| bfs(&map, start, |point| point == end, rules).map(|route| route.cost);
+        assert_eq!(steps(&MovementRules { diagonal: true, ..rules }), Some(27));
+        assert_eq!(steps(&MovementRules { max_ascent: 2, ..rules }), Some(27));
+        assert_eq!(steps(&MovementRules { max_ascent: 26, ..rules }), Some(7));
+        assert_eq!(steps(&MovementRules { max_descent: 0, ..rules }), Some(31));
+        assert_eq!(steps(&MovementRules { max_ascent: 0, ..rules }), None);
+
+        // the reversed rules lead back from E to S on the same route length
+        let diagonal = MovementRules { diagonal: true, ..rules };
+        assert_eq!(bfs(&map, end, |point| point == start, &diagonal.reversed()).map(|route| route.cost), Some(27));
+        assert_eq!(a_star(&map, start, end, &diagonal).map(|route| route.cost), Some(27));
+
+        let costs = MovementRules { step_cost: StepCost { per_ascent: 2, per_descent: 1 }, ..rules };
+        assert_eq!(costs.cost(5, 6), 3);
+        assert_eq!(costs.cost(6, 2), 5);
+        assert_eq!(costs.reversed().cost(6, 2), 9);
+    }
+}
@@ -1,4 +1,7 @@
 fn main() {
     println!("Solution 1: {}", day_12::solve_routine("input").unwrap());
     println!("Solution 1: {}", day_12::solve_routine_2("input").unwrap());
+    println!("Test Route: ");
+    let map = day_12::read_in_file_system("input_test").unwrap();
+    print!("{}", day_12::render_arrows(&map, &day_12::shortest_route(&map).unwrap()));
 }
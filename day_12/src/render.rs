@@ -0,0 +1,82 @@
+use std::collections::HashSet;
+use crate::{HeightMap, Point, Route};
+
+const PATH_COLOR: [u8; 3] = [220, 20, 20];
+
+/// The route in the puzzle's notation: every square on it shows the direction
+/// of the next step, the goal is `E` and all other squares are `.`. Diagonal
+/// steps have no arrow and are shown as `*`.
+pub fn render_arrows(map: &HeightMap, route: &Route) -> String {
+    let mut rows = vec![vec!['.'; map.width() as usize]; map.height() as usize];
+    for step in route.points.windows(2) {
+        let ((x, y), (next_x, next_y)) = (step[0], step[1]);
+        rows[y as usize][x as usize] = match (next_x - x, next_y - y) {
+            (1, 0) => '>',
+            (-1, 0) => '<',
+            (0, 1) => 'v',
+            (0, -1) => '^',
+            _ => '*'
+        };
+    }
+    if let Some(&(x, y)) = route.points.last() {
+        rows[y as usize][x as usize] = 'E';
+    }
+    rows.into_iter().map(|row| row.into_iter().collect::<String>() + "\n").collect()
+}
+
+/// Color of a square: from dark green at `a` over brown to white at `z`, or
+/// red if it lies on the route.
+fn color(map: &HeightMap, route: &HashSet<Point>, point: Point) -> [u8; 3] {
+    if route.contains(&point) {
+        return PATH_COLOR;
+    }
+    let t = (map.elevation(point).unwrap_or(1) - 1) as f64 / 25.0;
+    let low = [20.0, 90.0, 30.0];
+    let middle = [140.0, 100.0, 60.0];
+    let high = [255.0, 255.0, 255.0];
+    let (from, to, t) = if t < 0.5 { (low, middle, 2.0 * t) } else { (middle, high, 2.0 * t - 1.0) };
+    [0, 1, 2].map(|i| (from[i] + (to[i] - from[i]) * t).round() as u8)
+}
+
+/// RGB values of the image, row by row, every square `scale`×`scale` pixels.
+fn pixels(map: &HeightMap, route: Option<&Route>, scale: usize) -> Vec<u8> {
+    let route: HashSet<Point> = route.map_or(HashSet::new(), |route| route.points.iter().copied().collect());
+    let (width, height) = (map.width() as usize, map.height() as usize);
+    let mut data = Vec::with_capacity(width * height * scale * scale * 3);
+    for y in 0..height * scale {
+        for x in 0..width * scale {
+            data.extend(color(map, &route, ((x / scale) as i32, (y / scale) as i32)));
+        }
+    }
+    data
+}
+
+/// Plain (ASCII) PPM image of the elevations with the route drawn in red,
+/// or `None` if `scale` is 0.
+pub fn to_ppm(map: &HeightMap, route: Option<&Route>, scale: usize) -> Option<String> {
+    if scale == 0 {
+        return None;
+    }
+    let data = pixels(map, route, scale);
+    // a parsed map always has at least the start and end square
+    let width = map.width() as usize * scale;
+    let mut ppm = format!("P3\n{} {}\n255\n", width, map.height() as usize * scale);
+    for row in data.chunks(width * 3) {
+        let line: Vec<String> = row.iter().map(|value| value.to_string()).collect();
+        ppm.push_str(&line.join(" "));
+        ppm.push('\n');
+    }
+    Some(ppm)
+}
+
+/// Like `to_ppm`, as a PNG file. A `scale` of 0 gives an empty image, which
+/// the encoder rejects with a format error.
+pub fn to_png(map: &HeightMap, route: Option<&Route>, scale: usize) -> Result<Vec<u8>, png::EncodingError> {
+    let data = pixels(map, route, scale);
+    let mut bytes = Vec::new();
+    let mut encoder = png::Encoder::new(&mut bytes, (map.width() as usize * scale) as u32, (map.height() as usize * scale) as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(&data)?;
+    Ok(bytes)
+}
@@ -0,0 +1,153 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+use crate::{HeightMap, MovementRules, Point};
+
+/// A route found by a search: every point from the start to the goal, both
+/// included, and the summed up step costs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Route {
+    pub points: Vec<Point>,
+    pub cost: u32
+}
+
+impl Route {
+    /// Number of steps taken along the route.
+    pub fn steps(&self) -> usize {
+        self.points.len() - 1
+    }
+}
+
+/// Number of steps from the nearest source to every point of a heightmap.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DistanceMap {
+    width: i32,
+    distances: Vec<Option<u32>>
+}
+
+impl DistanceMap {
+    /// `None` if the point can not be reached from any source.
+    pub fn distance(&self, (x, y): Point) -> Option<u32> {
+        if 0 <= x && x < self.width && 0 <= y {
+            self.distances.get((y * self.width + x) as usize).copied().flatten()
+        } else {
+            None
+        }
+    }
+
+    /// All reachable points with their distance, row by row.
+    pub fn reachable(&self) -> impl Iterator<Item = (Point, u32)> + '_ {
+        self.distances.iter().enumerate().filter_map(move |(i, distance)| {
+            distance.map(|distance| (((i as i32) % self.width, (i as i32) / self.width), distance))
+        })
+    }
+
+    /// Points at most `steps` steps away from a source.
+    pub fn within(&self, steps: u32) -> impl Iterator<Item = Point> + '_ {
+        self.reachable().filter(move |&(_, distance)| distance <= steps).map(|(point, _)| point)
+    }
+}
+
+/// One breadth first search from all `sources` at once. To get the distance
+/// from every point to a target instead, start from the target and use the
+/// reversed rules (see `MovementRules::reversed`).
+pub fn distance_map<I>(map: &HeightMap, sources: I, rules: &MovementRules) -> DistanceMap
+    where I: IntoIterator<Item = Point> {
+    let mut distances = DistanceMap {
+        width: map.width(),
+        distances: vec![None; (map.width() * map.height()) as usize]
+    };
+    let mut queue = VecDeque::new();
+    for source in sources {
+        if let Some(i) = map.index(source) {
+            if distances.distances[i].is_none() {
+                distances.distances[i] = Some(0);
+                queue.push_back((source, 0));
+            }
+        }
+    }
+    while let Some((point, distance)) = queue.pop_front() {
+        for neighbour in map.neighbours(point, rules) {
+            let i = map.index(neighbour).unwrap();
+            if distances.distances[i].is_none() {
+                distances.distances[i] = Some(distance + 1);
+                queue.push_back((neighbour, distance + 1));
+            }
+        }
+    }
+    distances
+}
+
+/// Walks the predecessors back from `goal` to the start of the search.
+fn build_route(came_from: &HashMap<Point, Point>, goal: Point, cost: u32) -> Route {
+    let mut points = vec![goal];
+    while let Some(&previous) = came_from.get(points.last().unwrap()) {
+        points.push(previous);
+    }
+    points.reverse();
+    Route { points, cost }
+}
+
+/// Breadth first search for the nearest point satisfying `is_goal`. The step
+/// costs of the rules are ignored, every step costs 1.
+pub fn bfs<F>(map: &HeightMap, start: Point, is_goal: F, rules: &MovementRules) -> Option<Route>
+    where F: Fn(Point) -> bool {
+    let mut came_from = HashMap::new();
+    let mut queue = VecDeque::from([(start, 0)]);
+    while let Some((point, cost)) = queue.pop_front() {
+        if is_goal(point) {
+            return Some(build_route(&came_from, point, cost));
+        }
+        for neighbour in map.neighbours(point, rules) {
+            if neighbour != start && !came_from.contains_key(&neighbour) {
+                came_from.insert(neighbour, point);
+                queue.push_back((neighbour, cost + 1));
+            }
+        }
+    }
+    None
+}
+
+/// Cheapest route to a point satisfying `is_goal`, with the step costs of the
+/// rules.
+pub fn dijkstra<F>(map: &HeightMap, start: Point, is_goal: F, rules: &MovementRules) -> Option<Route>
+    where F: Fn(Point) -> bool {
+    best_first(map, start, is_goal, rules, |_| 0)
+}
+
+/// Like `dijkstra` for a single goal, guided by the Manhattan distance to it
+/// (the Chebyshev distance if diagonal steps are allowed). Every step costs
+/// at least 1, so the heuristic never overestimates.
+pub fn a_star(map: &HeightMap, start: Point, goal: Point, rules: &MovementRules) -> Option<Route> {
+    let diagonal = rules.diagonal;
+    let distance = |(x, y): Point| {
+        let (dx, dy) = ((x - goal.0).unsigned_abs(), (y - goal.1).unsigned_abs());
+        if diagonal { dx.max(dy) } else { dx + dy }
+    };
+    best_first(map, start, |point| point == goal, rules, distance)
+}
+
+fn best_first<F, H>(map: &HeightMap, start: Point, is_goal: F, rules: &MovementRules, heuristic: H) -> Option<Route>
+    where F: Fn(Point) -> bool, H: Fn(Point) -> u32 {
+    let mut costs = HashMap::from([(start, 0)]);
+    let mut came_from = HashMap::new();
+    let mut queue = BinaryHeap::from([(Reverse(heuristic(start)), Reverse(0), start)]);
+    while let Some((_, Reverse(cost), point)) = queue.pop() {
+        if costs[&point] < cost {
+            // a cheaper way to this point was found after it was queued
+            continue
+        }
+        if is_goal(point) {
+            return Some(build_route(&came_from, point, cost));
+        }
+        let elevation = map.elevation(point).unwrap();
+        for neighbour in map.neighbours(point, rules) {
+            let neighbour_cost = cost + rules.cost(elevation, map.elevation(neighbour).unwrap());
+            if costs.get(&neighbour).is_none_or(|&known| neighbour_cost < known) {
+                costs.insert(neighbour, neighbour_cost);
+                came_from.insert(neighbour, point);
+                queue.push((Reverse(neighbour_cost + heuristic(neighbour)), Reverse(neighbour_cost), neighbour));
+            }
+        }
+    }
+    None
+}